@@ -12,7 +12,8 @@ pub struct AnimationPlugin;
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_system(process_commands);
+        app.add_event::<AnimationMarker>()
+            .add_system(process_commands);
     }
 }
 
@@ -83,9 +84,17 @@ pub enum AnimationCommand {
     Wait(f32),
     MoveBy([f32; 2], f32),
     Pause(),
+    /// Sends an [AnimationMarker] event when reached.
+    Marker(String),
     // Unpause(),
 }
 
+/// Sent when an animator reaches a [AnimationCommand::Marker] in its queue.
+pub struct AnimationMarker {
+    pub entity: Entity,
+    pub marker: String,
+}
+
 #[derive(Component, Default)]
 pub struct Animator {
     queue: VecDeque<AnimationCommand>,
@@ -144,6 +153,11 @@ impl Animator {
         self
     }
 
+    /// Whether the animator has finished all of it's queued commands.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.state.is_none()
+    }
+
     pub fn push_cmds_back(
         &mut self,
         commands: impl IntoIterator<Item = AnimationCommand>,
@@ -167,6 +181,7 @@ fn process_commands(
     time: Res<Time>,
     mut q_anim_commands: Query<(Entity, &mut Animator, &mut TextureAtlasSprite, &mut Transform)>,
     q_name: Query<&Name>,
+    mut markers: EventWriter<AnimationMarker>,
 ) {
     let dt = time.delta();
 
@@ -203,6 +218,13 @@ fn process_commands(
                         driver.pause_animation();
                         None
                     }
+                    AnimationCommand::Marker(marker) => {
+                        markers.send(AnimationMarker {
+                            entity,
+                            marker: marker.to_owned(),
+                        });
+                        None
+                    }
                 };
                 //println!("Changing state to {:?}", next);
                 driver.state = next;
@@ -269,6 +291,13 @@ fn from_str() {
     let cmds: AnimationCommand = ron::de::from_str(str).unwrap();
 }
 
+#[test]
+fn marker() {
+    let str = "Marker(\"resolve\")";
+    let cmd: AnimationCommand = ron::de::from_str(str).unwrap();
+    assert_eq!(cmd, AnimationCommand::Marker("resolve".to_string()));
+}
+
 #[test]
 fn play() {
     let str = "Play(\"AnimName\")";
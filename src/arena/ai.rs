@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use rand::{prelude::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ldtk_loader::Fields,
+    unit::{physical_damage, Shields, Stats},
+};
+
+use super::{card_asset::CardAsset, cards::CardEffect, damage::elemental_damage};
+
+/// How an enemy unit picks its card and target in the arena. Set per prefab
+/// through the `ai` field on the root entity.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AiStrategy {
+    /// Any attack on any target.
+    Random,
+    /// The attack and target that deal the most damage.
+    Greedy,
+    /// The hardest attack on the target with the least hp.
+    FinishLowest,
+}
+
+impl Default for AiStrategy {
+    fn default() -> Self {
+        AiStrategy::Random
+    }
+}
+
+impl AiStrategy {
+    pub fn from_name(name: &str) -> Option<AiStrategy> {
+        match name.to_lowercase().as_str() {
+            "random" => Some(AiStrategy::Random),
+            "greedy" => Some(AiStrategy::Greedy),
+            "finish_lowest" => Some(AiStrategy::FinishLowest),
+            _ => None,
+        }
+    }
+
+    /// Read the `ai` field from a unit prefab's root entity.
+    pub fn from_fields(fields: &Fields) -> Self {
+        fields
+            .try_get_str("ai")
+            .and_then(AiStrategy::from_name)
+            .unwrap_or_default()
+    }
+}
+
+/// A unit the AI could attack.
+pub struct AiTarget<'a> {
+    pub entity: Entity,
+    pub stats: &'a Stats,
+    pub shields: &'a Shields,
+}
+
+/// Pick a card from `hand` and one of `targets` to play it on. Only attacks are
+/// considered. Returns the index of the card in `hand` and the target.
+pub fn choose_play(
+    strategy: AiStrategy,
+    actor: &Stats,
+    hand: &[&CardAsset],
+    targets: &[AiTarget],
+    rng: &mut impl Rng,
+) -> Option<(usize, Entity)> {
+    let attacks: Vec<_> = (0..hand.len())
+        .filter(|i| !hand[*i].targets_allies())
+        .collect();
+    if attacks.is_empty() || targets.is_empty() {
+        return None;
+    }
+
+    match strategy {
+        AiStrategy::Random => {
+            let card = *attacks.choose(rng)?;
+            let target = targets.choose(rng)?;
+            Some((card, target.entity))
+        }
+        AiStrategy::Greedy => {
+            let mut best = None;
+            let mut best_damage = -1;
+            for card in attacks.iter() {
+                for target in targets.iter() {
+                    let damage = estimate_damage(hand[*card], actor, target);
+                    if damage > best_damage {
+                        best_damage = damage;
+                        best = Some((*card, target.entity));
+                    }
+                }
+            }
+            best
+        }
+        AiStrategy::FinishLowest => {
+            let target = targets.iter().min_by_key(|t| t.stats.hp())?;
+            let card = attacks
+                .iter()
+                .rev()
+                .max_by_key(|card| estimate_damage(hand[**card], actor, target))?;
+            Some((*card, target.entity))
+        }
+    }
+}
+
+/// Damage `card` would deal to `target` if played by `actor`.
+pub fn estimate_damage(card: &CardAsset, actor: &Stats, target: &AiTarget) -> i32 {
+    card.effects
+        .iter()
+        .map(|effect| match effect {
+            CardEffect::Damage(damage) => physical_damage(*damage, actor, target.stats),
+            CardEffect::ElementalDamage { element, damage } => elemental_damage(
+                *damage,
+                *element,
+                actor,
+                target.stats,
+                target.shields.has(*element),
+            ),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+use crate::unit::Element;
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+#[cfg(test)]
+fn card(effects: Vec<CardEffect>) -> CardAsset {
+    CardAsset {
+        title: String::default(),
+        rarity: Default::default(),
+        element: Element::Fire,
+        cost: 0,
+        effects,
+    }
+}
+
+#[test]
+fn greedy_picks_most_damage() {
+    let actor = Stats::new(10, 0, 0);
+    let weak = card(vec![CardEffect::Damage(2)]);
+    let strong = card(vec![CardEffect::Damage(6)]);
+    let heal = card(vec![CardEffect::Heal(20)]);
+    let hand = [&weak, &heal, &strong];
+
+    let (a, b) = (Stats::new(10, 0, 3), Stats::new(10, 0, 0));
+    let shields = Shields::default();
+    let targets = [
+        AiTarget { entity: Entity::from_raw(0), stats: &a, shields: &shields },
+        AiTarget { entity: Entity::from_raw(1), stats: &b, shields: &shields },
+    ];
+
+    let play = choose_play(AiStrategy::Greedy, &actor, &hand, &targets, &mut StdRng::seed_from_u64(0));
+    assert_eq!(play, Some((2, Entity::from_raw(1))));
+}
+
+#[test]
+fn greedy_avoids_shields() {
+    let actor = Stats::new(10, 0, 0);
+    let fire = card(vec![CardEffect::ElementalDamage { element: Element::Fire, damage: 8 }]);
+    let holy = card(vec![CardEffect::ElementalDamage { element: Element::Holy, damage: 4 }]);
+    let hand = [&fire, &holy];
+
+    let stats = Stats::new(10, 0, 0);
+    let mut shields = Shields::default();
+    shields.add(Element::Fire);
+    let targets = [AiTarget { entity: Entity::from_raw(0), stats: &stats, shields: &shields }];
+
+    let play = choose_play(AiStrategy::Greedy, &actor, &hand, &targets, &mut StdRng::seed_from_u64(0));
+    assert_eq!(play, Some((1, Entity::from_raw(0))));
+}
+
+#[test]
+fn finish_lowest_targets_weakest() {
+    let actor = Stats::new(10, 0, 0);
+    let weak = card(vec![CardEffect::Damage(2)]);
+    let strong = card(vec![CardEffect::Damage(5)]);
+    let hand = [&weak, &strong];
+
+    let mut hurt = Stats::new(10, 0, 0);
+    hurt.take_damage(8);
+    let healthy = Stats::new(10, 0, 0);
+    let shields = Shields::default();
+    let targets = [
+        AiTarget { entity: Entity::from_raw(0), stats: &healthy, shields: &shields },
+        AiTarget { entity: Entity::from_raw(1), stats: &hurt, shields: &shields },
+    ];
+
+    let play = choose_play(AiStrategy::FinishLowest, &actor, &hand, &targets, &mut StdRng::seed_from_u64(0));
+    assert_eq!(play, Some((1, Entity::from_raw(1))));
+}
+
+#[test]
+fn random_is_deterministic_with_seed() {
+    let actor = Stats::new(10, 0, 0);
+    let cards: Vec<_> = (1..6).map(|d| card(vec![CardEffect::Damage(d)])).collect();
+    let hand: Vec<_> = cards.iter().collect();
+    let stats = Stats::new(10, 0, 0);
+    let shields = Shields::default();
+    let targets: Vec<_> = (0..4)
+        .map(|i| AiTarget { entity: Entity::from_raw(i), stats: &stats, shields: &shields })
+        .collect();
+
+    let a = choose_play(AiStrategy::Random, &actor, &hand, &targets, &mut StdRng::seed_from_u64(7));
+    let b = choose_play(AiStrategy::Random, &actor, &hand, &targets, &mut StdRng::seed_from_u64(7));
+    assert!(a.is_some());
+    assert_eq!(a, b);
+}
+
+#[test]
+fn no_attacks_no_play() {
+    let actor = Stats::new(10, 0, 0);
+    let heal = card(vec![CardEffect::Heal(3)]);
+    let stats = Stats::new(10, 0, 0);
+    let shields = Shields::default();
+    let targets = [AiTarget { entity: Entity::from_raw(0), stats: &stats, shields: &shields }];
+
+    let play = choose_play(AiStrategy::Greedy, &actor, &[&heal], &targets, &mut StdRng::seed_from_u64(0));
+    assert_eq!(play, None);
+}
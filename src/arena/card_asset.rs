@@ -0,0 +1,93 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::unit::Element;
+
+use super::cards::CardEffect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+}
+
+impl Default for Rarity {
+    fn default() -> Self {
+        Rarity::Common
+    }
+}
+
+impl Rarity {
+    /// How likely a card of this rarity is to be offered as a reward.
+    pub fn weight(&self) -> f64 {
+        match self {
+            Rarity::Common => 6.0,
+            Rarity::Uncommon => 3.0,
+            Rarity::Rare => 1.0,
+        }
+    }
+}
+
+/// A card definition loaded from a `.card` file in `assets/abilities`.
+///
+/// Cards are referred to by their asset path, ie "abilities/fireball.card".
+#[derive(TypeUuid, Debug, Clone, Serialize, Deserialize)]
+#[uuid = "5b1f6a2e-23cd-4c0e-9a5d-3f7e2c41b8d6"]
+pub struct CardAsset {
+    pub title: String,
+    #[serde(default)]
+    pub rarity: Rarity,
+    pub element: Element,
+    #[serde(default)]
+    pub cost: i32,
+    pub effects: Vec<CardEffect>,
+}
+
+impl CardAsset {
+    /// Whether the card should be played on the caster's own party.
+    pub fn targets_allies(&self) -> bool {
+        self.effects.iter().all(CardEffect::targets_allies)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CardAssetLoader;
+
+impl AssetLoader for CardAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let card: CardAsset = ron::de::from_bytes(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(card));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["card"]
+    }
+}
+
+#[test]
+fn parse() {
+    let str = "(
+        title: \"Fireball\",
+        rarity: Uncommon,
+        element: Fire,
+        effects: [ ElementalDamage(element: Fire, damage: 4) ],
+    )";
+    let card: CardAsset = ron::de::from_str(str).unwrap();
+    assert_eq!(card.rarity, Rarity::Uncommon);
+    assert_eq!(card.cost, 0);
+    assert!(!card.targets_allies());
+}
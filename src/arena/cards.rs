@@ -1,39 +1,96 @@
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContext};
-use bevy_tiled_camera::TiledProjection;
+use serde::{Deserialize, Serialize};
 
+use crate::config::ConfigAsset;
+use crate::ldtk_loader::{EntitiesLayer, LdtkMap, PrefabEntity};
+use crate::party::PartyUnit;
 use crate::unit::Element;
 use crate::util::*;
-use crate::GameState;
+use crate::{GameState, SETTINGS_PATH};
+
+use super::card_asset::{CardAsset, CardAssetLoader};
+use super::combat::{unit_at, valid_targets, ArenaTurn, TurnOrder};
+use super::deck::Deck;
+use super::{ArenaCombat, ArenaState};
+
+/// How much a card grows when hovered.
+const HOVER_SCALE: f32 = 1.25;
+/// How far a card is lifted out of the hand when hovered.
+const HOVER_RAISE: f32 = 60.0;
+const HOVER_DEPTH: f32 = 20.0;
+const DRAG_DEPTH: f32 = 100.0;
 
 pub struct CardsPlugin;
 
 impl Plugin for CardsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Arena).with_system(card_ui));
+        app.add_asset::<CardAsset>()
+            .add_asset_loader(CardAssetLoader)
+            .init_resource::<CardLayout>()
+            .add_event::<CardPlayed>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Arena)
+                    .with_system(spawn_cards)
+                    .with_system(card_ui),
+            )
+            .add_system_set(SystemSet::on_enter(ArenaState::ChooseCard).with_system(show_hand))
+            .add_system_set(
+                SystemSet::on_update(ArenaState::ChooseCard).with_system(card_interaction),
+            )
+            .add_system_set(SystemSet::on_enter(ArenaState::Resolving).with_system(clear_hand))
+            .add_system_set(SystemSet::on_exit(GameState::Arena).with_system(clear_hand));
     }
 }
+
+/// A card on the table. `path` is the card's asset path.
 #[derive(Component)]
-pub struct Card;
+pub struct Card {
+    pub path: String,
+    home: Vec3,
+    size: Vec2,
+}
+
+impl Card {
+    /// Where the card rests in the hand.
+    pub fn home(&self) -> Vec3 {
+        self.home
+    }
+
+    fn contains(&self, transform: &Transform, point: Vec2) -> bool {
+        contains(
+            transform.translation.truncate(),
+            self.size * transform.scale.truncate(),
+            point,
+        )
+    }
+}
+
+/// Sent when a card from the hand is dropped on a valid target.
+pub struct CardPlayed {
+    pub card: String,
+    pub source: Entity,
+    pub target: Entity,
+}
 
 fn card_ui(
     mut egui: ResMut<EguiContext>,
-    q_cam: Query<(&Camera, &TiledProjection, &GlobalTransform)>,
+    q_cam: Query<(&Camera, &GlobalTransform)>,
     windows: Res<Windows>,
-    q_cards: Query<(Entity, &Transform), With<Card>>,
     q_labels: Query<(Entity, &CardLabel, &CardLabelType, &GlobalTransform)>,
 ) {
-    if let Ok((cam, proj, cam_transform)) = q_cam.get_single() {
+    if let (Ok((cam, cam_transform)), Some(window)) = (q_cam.get_single(), windows.get_primary()) {
         for (entity, label, ltype, ltransform) in q_labels.iter() {
-            let mut p = ltransform.translation.xy();
-            p.y = -p.y;
+            let p = ltransform.translation.xy();
             //println!("P {}", p);
-            if let Some(p) = proj.world_to_screen(cam, &windows, cam_transform, p.extend(0.0)) {
-                egui::containers::Area::new(&label.label_name)
+            if let Some(mut p) = cam.world_to_screen(&windows, cam_transform, p.extend(0.0)) {
+                // Egui's origin is the top left of the window
+                p.y = window.height() - p.y;
+                egui::containers::Area::new(entity)
                     .fixed_pos(p.to_array())
                     .show(egui.ctx_mut(), |ui| {
-                        ui.label(&label.label_name);
+                        ui.label(&label.label_content);
                     });
             }
         }
@@ -68,11 +125,37 @@ fn card_ui(
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum CardEffect {
+    Damage(i32),
+    Heal(i32),
     ElementalDamage { element: Element, damage: i32 },
     ElementalShield { element: Element },
 }
 
+impl CardEffect {
+    /// Whether the effect should be played on the caster's own party.
+    pub fn targets_allies(&self) -> bool {
+        match self {
+            CardEffect::Damage(_) | CardEffect::ElementalDamage { .. } => false,
+            CardEffect::Heal(_) | CardEffect::ElementalShield { .. } => true,
+        }
+    }
+}
+
+impl std::fmt::Display for CardEffect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardEffect::Damage(damage) => write!(f, "Deal {} damage", damage),
+            CardEffect::Heal(amount) => write!(f, "Heal {}", amount),
+            CardEffect::ElementalDamage { element, damage } => {
+                write!(f, "Deal {} {:?} damage", damage, element)
+            }
+            CardEffect::ElementalShield { element } => write!(f, "Block {:?} damage", element),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct CardLabel {
     label_name: String,
@@ -91,9 +174,18 @@ impl CardLabel {
         }
     }
 
+    pub fn with_content(mut self, content: String) -> Self {
+        self.label_content = content;
+        self
+    }
+
     pub fn xy(&self) -> Vec2 {
         self.xy
     }
+
+    pub fn content(&self) -> &str {
+        &self.label_content
+    }
 }
 
 #[derive(Debug, Component, Copy, Clone, Eq, PartialEq)]
@@ -103,9 +195,12 @@ pub enum CardLabelType {
     Ability(i32), // int refers to which ability it is since cards can have multiple
 }
 
+/// Spawns a card entity for the card asset at `card`, ie "abilities/fireball.card".
 #[derive(Component)]
 pub struct SpawnCard {
-    pub element: Element,
+    pub card: String,
+    pub xyz: Vec3,
+    pub size: Vec2,
 }
 
 // Elements of a card:
@@ -118,112 +213,306 @@ root (sprite, area)
 */
 
 #[derive(Default)]
-pub struct CardsAtlas(Handle<TextureAtlas>);
-
-// /// Build a card entity with it's labels as child entities
-// fn spawn_card(
-//     commands: &mut Commands,
-//     layer: &EntitiesLayer,
-//     atlas: Handle<TextureAtlas>,
-//     element: Element,
-//     font: Handle<Font>,
-// ) -> Entity {
-//     let root = layer.get_from_name("card")
-//         .expect("Couldn't find prefab 'card' in layer");
-//     let title = layer.get_from_name("card_title")
-//         .expect("Couldn't find 'card_title' in layer");
-//     let rarity = layer.get_from_name("card_rarity")
-//         .expect("Couldn't find 'card_rarity' in layer");
-//     let abilities = layer.get_all_from_name("card_abilities");
-
-//     let mut xy = root.pixel_xy().as_vec2();
-//     let size = root.size().as_vec2();
-
-//     println!("XY: {}", xy);
-//     //let root_offset = -(Vec2::new(0.0, size.y) / 2.0);
-//     //xy += root_offset;
-//     //let root_offset = Vec2::ZERO;//-root_offset * 4.0;
-//     println!("Spawning card at {}, size {}", xy, size);
-//     let depth = 11;
-//     let root = make_sprite_atlas_sized(
-//         commands,
-//         xy,
-//         size,
-//         depth,
-//         atlas,
-//         element.get_sprite_id(),
-//     ).id();
-
-//     let title = get_label_entity(commands, title);
-//     let rarity = get_label_entity(commands, rarity);
-
-//     let mut children = Vec::new();
-
-//     children.push(title);
-
-//     for (i,ability) in abilities.enumerate() {
-//         let ability = get_label_entity(commands, ability);
-//     }
-
-//     commands.entity(root).push_children(
-//         &children
-//     );
-//     root
-// }
-
-// fn find_entity<'a>(
-//     entities: &'a Vec<MapEntity>,
-//     name: &str
-// ) -> &'a MapEntity {
-//     entities.iter().find(|e|e.name() == name).unwrap_or_else(||
-//         panic!("Arena build error: Couldn't find entity '{}'", name)
-//     )
-// }
-
-// fn find_all_entities<'a>(
-//     entities: &'a Vec<MapEntity>,
-//     name: &'a str
-// ) -> impl Iterator<Item=&'a MapEntity> {
-//     entities.iter().filter(move |e|e.name()==name)
-// }
-
-// fn get_label_entity(commands: &mut Commands, label: &MapEntity) -> Entity {
-//     let xyz = get_label_pos(label);
-//     let title = get_label(label, &label.name());
-//     commands.spawn().insert(title)
-//     .insert(CardLabelType::Title)
-//     .insert(Transform::from_translation(xyz))
-//     .insert(GlobalTransform::default())
-//     .id()
-// }
-
-// fn get_label(entity: &MapEntity, name: &str) -> CardLabel {
-//     let area = get_area(entity);
-//     CardLabel::new(name, area)
-// }
-
-// fn get_label_pos(label: &MapEntity) -> Vec3 {
-//     // let xy = label.fields.get("offset").unwrap_or_else(||
-//     //     panic!("Couldn't find offset fields for entity {}", label.name)
-//     // );
-
-//     // let xy = xy.as_array().unwrap_or_else(||
-//     //     panic!("Offset field was an unexpected type! Should be an Array"));
-//     // let xy = xy.iter().map(|v|v.as_f64().unwrap() as f32);
-//     // let xy: Vec<f32> = xy.collect();
-//     // Vec2::new(xy[0], xy[1]).extend(0.0)
-//     Vec3::ZERO
-// }
-
-// fn get_area(entity: &MapEntity) -> Rect<f32> {
-//     let xy = entity.pixel_xy().as_vec2();
-//     let size = entity.size().as_vec2();
-//     //todo!()
-//     // let offset = Vec2::new(0.0,0.0);
-//     Rect {
-//         left: xy.x ,
-//         right: xy.x + size.x ,
-//         top: xy.y + size.y,
-//         bottom: xy.y,
-//     }
-// }
+pub struct CardsAtlas(pub Handle<TextureAtlas>);
+
+/// Where a card's labels sit, relative to the center of the card. Read from
+/// the "Card_Data" layer of the arena file.
+pub struct CardLayout {
+    title: Rect<f32>,
+    rarity: Rect<f32>,
+    abilities: Vec<Rect<f32>>,
+}
+
+impl Default for CardLayout {
+    fn default() -> Self {
+        Self {
+            title: label_area(Vec2::new(0.0, 190.0), Vec2::new(240.0, 30.0)),
+            rarity: label_area(Vec2::new(0.0, 155.0), Vec2::new(240.0, 32.0)),
+            abilities: vec![
+                label_area(Vec2::new(0.0, -60.0), Vec2::new(240.0, 60.0)),
+                label_area(Vec2::new(0.0, -130.0), Vec2::new(240.0, 60.0)),
+            ],
+        }
+    }
+}
+
+impl CardLayout {
+    /// Read the layout from the `card`, `card_title`, `card_rarity` and
+    /// `card_abilities` entities in the given layer.
+    pub fn from_layer(layer: &EntitiesLayer) -> Option<Self> {
+        let root = layer.get_from_name("card")?;
+        let origin = root.xy().as_vec2();
+        let area = |e: &PrefabEntity| label_area(e.xy().as_vec2() - origin, e.size().as_vec2());
+
+        let title = area(layer.get_from_name("card_title")?);
+        let rarity = area(layer.get_from_name("card_rarity")?);
+        let mut abilities: Vec<_> = layer.get_all_from_name("card_abilities").map(area).collect();
+        if abilities.is_empty() {
+            return None;
+        }
+        // Top to bottom
+        abilities.sort_by(|a, b| b.top.partial_cmp(&a.top).unwrap());
+
+        Some(Self {
+            title,
+            rarity,
+            abilities,
+        })
+    }
+
+    pub fn from_ldtk(ldtk: &LdtkMap) -> Self {
+        ldtk.layer_from_name("card_data")
+            .and_then(|layer| layer.as_entities())
+            .and_then(CardLayout::from_layer)
+            .unwrap_or_else(|| {
+                warn!(
+                    "Couldn't find card layout entities in 'Card_Data' layer of {}, using default layout",
+                    ldtk.name()
+                );
+                CardLayout::default()
+            })
+    }
+
+    /// Area for the ability at `index`. Abilities past the ones in the layout are
+    /// stacked below the last one.
+    fn ability(&self, index: usize) -> Rect<f32> {
+        let last = self.abilities.len() - 1;
+        let mut area = self.abilities[index.min(last)];
+        if index > last {
+            let offset = (area.top - area.bottom) * (index - last) as f32;
+            area.top -= offset;
+            area.bottom -= offset;
+        }
+        area
+    }
+}
+
+fn label_area(center: Vec2, size: Vec2) -> Rect<f32> {
+    let half = size / 2.0;
+    Rect {
+        left: center.x - half.x,
+        right: center.x + half.x,
+        top: center.y + half.y,
+        bottom: center.y - half.y,
+    }
+}
+
+/// Build a card entity with it's labels as child entities
+fn spawn_cards(
+    mut commands: Commands,
+    atlas: Res<CardsAtlas>,
+    layout: Res<CardLayout>,
+    cards: Res<Assets<CardAsset>>,
+    q_spawn: Query<(Entity, &SpawnCard)>,
+) {
+    for (entity, spawn) in q_spawn.iter() {
+        let card = match cards.get(spawn.card.as_str()) {
+            Some(card) => card,
+            None => {
+                warn!("Couldn't spawn card {}, it isn't loaded", spawn.card);
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+
+        let mut children = vec![
+            spawn_label(&mut commands, "title", layout.title, CardLabelType::Title, card.title.clone()),
+            spawn_label(
+                &mut commands,
+                "rarity",
+                layout.rarity,
+                CardLabelType::Rarity,
+                format!("{:?}", card.rarity),
+            ),
+        ];
+        for (i, effect) in card.effects.iter().enumerate() {
+            children.push(spawn_label(
+                &mut commands,
+                "ability",
+                layout.ability(i),
+                CardLabelType::Ability(i as i32),
+                effect.to_string(),
+            ));
+        }
+
+        commands
+            .entity(entity)
+            .remove::<SpawnCard>()
+            .insert_bundle(make_spritesheet_bundle(
+                card.element.get_sprite_id(),
+                atlas.0.clone(),
+                spawn.xyz,
+            ))
+            .insert(Card {
+                path: spawn.card.clone(),
+                home: spawn.xyz,
+                size: spawn.size,
+            })
+            .push_children(&children);
+    }
+}
+
+fn spawn_label(
+    commands: &mut Commands,
+    name: &str,
+    area: Rect<f32>,
+    ltype: CardLabelType,
+    content: String,
+) -> Entity {
+    let label = CardLabel::new(name, area).with_content(content);
+    commands
+        .spawn()
+        .insert(Transform::from_xyz(area.left, area.top, 1.0))
+        .insert(GlobalTransform::default())
+        .insert(label)
+        .insert(ltype)
+        .id()
+}
+
+/// Lay the acting unit's hand out on the arena's card spawn points.
+fn show_hand(
+    mut commands: Commands,
+    turn: Res<ArenaTurn>,
+    ldtk: Res<Assets<LdtkMap>>,
+    config: Res<Assets<ConfigAsset>>,
+    q_parent: Query<&Parent>,
+    q_deck: Query<&Deck>,
+    q_cards: Query<Entity, Or<(With<Card>, With<SpawnCard>)>>,
+) {
+    for entity in q_cards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let ldtk = match config
+        .get(SETTINGS_PATH)
+        .and_then(|config| ldtk.get(&config.settings.arena_file))
+    {
+        Some(ldtk) => ldtk,
+        None => return,
+    };
+    let actor = match turn.actor {
+        Some(actor) => actor,
+        None => return,
+    };
+    let hand = match q_parent.get(actor).and_then(|p| q_deck.get(p.0)) {
+        Ok(deck) => deck.hand(),
+        Err(_) => return,
+    };
+
+    let mut spawns: Vec<_> = ldtk
+        .get_tagged("spawn_point")
+        .filter(|e| e.tags().has("card"))
+        .collect();
+    spawns.sort_by_key(|e| e.fields().try_get_i32("spawnid").unwrap_or(0));
+
+    if hand.len() > spawns.len() {
+        warn!(
+            "Hand has {} cards but there are only {} card spawn points",
+            hand.len(),
+            spawns.len()
+        );
+    }
+
+    for (i, (spawn, card)) in spawns.iter().zip(hand.iter()).enumerate() {
+        commands.spawn().insert(SpawnCard {
+            card: card.to_owned(),
+            xyz: spawn.xy().as_vec2().extend(40.0 + i as f32),
+            size: spawn.size().as_vec2(),
+        });
+    }
+}
+
+fn clear_hand(mut commands: Commands, q_cards: Query<Entity, Or<(With<Card>, With<SpawnCard>)>>) {
+    for entity in q_cards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[derive(Default)]
+struct CardDrag {
+    card: Option<Entity>,
+    offset: Vec2,
+}
+
+/// Hovered cards are lifted out of the hand and can be dragged onto a unit.
+/// Dropping a card anywhere but a valid target puts it back in the hand.
+fn card_interaction(
+    mut drag: Local<CardDrag>,
+    mut events: EventWriter<CardPlayed>,
+    input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    turn: Res<ArenaTurn>,
+    order: Res<TurnOrder>,
+    cards: Res<Assets<CardAsset>>,
+    q_cam: Query<(&Camera, &GlobalTransform)>,
+    q_combat: Query<&ArenaCombat>,
+    q_parent: Query<&Parent>,
+    q_unit: Query<&PartyUnit>,
+    q_sprite: Query<(&GlobalTransform, &TextureAtlasSprite)>,
+    mut q_cards: Query<(Entity, &Card, &mut Transform)>,
+) {
+    let cursor = match (q_cam.get_single(), windows.get_primary()) {
+        (Ok((cam, cam_transform)), Some(window)) => window
+            .cursor_position()
+            .and_then(|pos| screen_to_world(cam, &windows, cam_transform, pos)),
+        _ => None,
+    };
+    let cursor = match cursor {
+        Some(cursor) => cursor.truncate(),
+        None => return,
+    };
+
+    if let Some(dragged) = drag.card {
+        if let Ok((_, card, mut transform)) = q_cards.get_mut(dragged) {
+            if input.pressed(MouseButton::Left) {
+                transform.translation = (cursor + drag.offset).extend(DRAG_DEPTH);
+                return;
+            }
+
+            let target = turn.actor.zip(q_combat.get_single().ok()).and_then(|(actor, combat)| {
+                let asset = cards.get(card.path.as_str())?;
+                let targets = valid_targets(combat, &order, &q_parent, actor, asset);
+                unit_at(cursor, &targets, &q_unit, &q_sprite).map(|target| (actor, target))
+            });
+
+            match target {
+                Some((source, target)) => events.send(CardPlayed {
+                    card: card.path.clone(),
+                    source,
+                    target,
+                }),
+                None => {
+                    transform.translation = card.home;
+                    transform.scale = Vec3::ONE;
+                }
+            }
+        }
+        drag.card = None;
+        return;
+    }
+
+    let hovered = q_cards
+        .iter()
+        .filter(|(_, card, transform)| card.contains(transform, cursor))
+        .max_by(|(_, _, a), (_, _, b)| a.translation.z.partial_cmp(&b.translation.z).unwrap())
+        .map(|(entity, ..)| entity);
+
+    for (entity, card, mut transform) in q_cards.iter_mut() {
+        if Some(entity) == hovered {
+            transform.translation = card.home + Vec3::new(0.0, HOVER_RAISE, HOVER_DEPTH);
+            transform.scale = Vec3::splat(HOVER_SCALE);
+            if input.just_pressed(MouseButton::Left) {
+                drag.card = Some(entity);
+                drag.offset = transform.translation.truncate() - cursor;
+            }
+        } else {
+            transform.translation = card.home;
+            transform.scale = Vec3::ONE;
+        }
+    }
+}
+
+fn contains(center: Vec2, size: Vec2, point: Vec2) -> bool {
+    let d = (point - center).abs();
+    d.x <= size.x / 2.0 && d.y <= size.y / 2.0
+}
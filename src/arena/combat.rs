@@ -1,27 +1,679 @@
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
 
 use crate::{
-    animation::{AnimationCommand, Animator},
-    GameState,
+    animation::{AnimationCommand, AnimationMarker, Animator},
+    party::PartyUnit,
+    rng::{GameRng, ARENA_AI_STREAM, DECK_STREAM},
+    screen_to_world,
+    unit::{Shields, Stats},
+    ArenaSpriteVisibility, GameState, TILE_SIZE,
+};
+
+use super::{
+    ai::{choose_play, AiStrategy, AiTarget},
+    card_asset::CardAsset,
+    cards::{CardEffect, CardPlayed},
+    deck::{Deck, HAND_SIZE},
+    results::CombatResults,
+    terrain::TerrainModifiers,
+    ArenaCombat, ArenaEntity, ArenaState, TakingATurn,
 };
 
-use super::TakingATurn;
+/// Tints for units that can be targeted by the chosen card.
+const ENEMY_TARGET_TINT: Color = Color::rgb(1.0, 0.6, 0.6);
+const ALLY_TARGET_TINT: Color = Color::rgb(0.6, 1.0, 0.6);
 
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(GameState::Arena).with_system(do_turn));
+        app.init_resource::<TurnOrder>()
+            .init_resource::<ArenaTurn>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Arena).with_system(roll_initiative),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Arena)
+                    .with_system(start_turn)
+                    .with_system(play_card)
+                    .with_system(do_turn)
+                    .with_system(remove_dead_units)
+                    .with_system(end_combat),
+            )
+            .add_system_set(
+                SystemSet::on_update(ArenaState::ChooseCard).with_system(choose_card),
+            )
+            .add_system_set(
+                SystemSet::on_enter(ArenaState::SelectTarget).with_system(highlight_targets),
+            )
+            .add_system_set(
+                SystemSet::on_update(ArenaState::SelectTarget).with_system(select_target),
+            )
+            .add_system_set(
+                SystemSet::on_exit(ArenaState::SelectTarget).with_system(clear_highlights),
+            )
+            .add_system_set(
+                SystemSet::on_update(ArenaState::EnemyTurn).with_system(enemy_turn),
+            );
+    }
+}
+
+/// Units from both parties in the order they act. The unit at the front
+/// is cycled to the back when it takes its turn.
+#[derive(Default, Debug)]
+pub struct TurnOrder {
+    queue: VecDeque<Entity>,
+}
+
+impl TurnOrder {
+    pub fn remove(&mut self, unit: Entity) {
+        self.queue.retain(|e| *e != unit);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.queue.iter()
+    }
+}
+
+/// Marks a unit the chosen card can be played on.
+#[derive(Component)]
+pub struct TargetHighlight;
+
+/// The unit currently acting and the choices made for it so far. Cards
+/// are referred to by their asset path.
+#[derive(Default, Debug)]
+pub struct ArenaTurn {
+    pub actor: Option<Entity>,
+    pub card: Option<String>,
+    pub target: Option<Entity>,
+    /// Whether the actor's attack script has been started.
+    pub animating: bool,
+    /// Whether the card's effects have been applied.
+    pub resolved: bool,
+}
+
+/// The script a unit's arena sprite plays when it takes its turn. Read from
+/// the unit's spawn point in the arena file.
+#[derive(Component, Default, Debug, Clone)]
+pub struct AttackActions(pub Vec<AnimationCommand>);
+
+/// Marker in an attack script at which the card's effects are applied.
+pub const RESOLVE_MARKER: &str = "resolve";
+
+/// Alternate between player and enemy units, player first.
+fn roll_initiative(
+    mut order: ResMut<TurnOrder>,
+    mut turn: ResMut<ArenaTurn>,
+    mut results: ResMut<CombatResults>,
+    q_combat: Query<&ArenaCombat>,
+    q_children: Query<&Children>,
+    q_unit: Query<&PartyUnit>,
+) {
+    order.queue.clear();
+    *turn = ArenaTurn::default();
+    *results = CombatResults::default();
+
+    if let Ok(combat) = q_combat.get_single() {
+        let units = |party: Entity| -> Vec<Entity> {
+            match q_children.get(party) {
+                Ok(children) => children
+                    .iter()
+                    .filter(|e| q_unit.get(**e).is_ok())
+                    .cloned()
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        };
+        let player = units(combat.player_party);
+        let enemy = units(combat.enemy_party);
+
+        for i in 0..player.len().max(enemy.len()) {
+            if let Some(unit) = player.get(i) {
+                order.queue.push_back(*unit);
+            }
+            if let Some(unit) = enemy.get(i) {
+                order.queue.push_back(*unit);
+            }
+        }
+    }
+}
+
+fn start_turn(
+    mut commands: Commands,
+    mut order: ResMut<TurnOrder>,
+    mut turn: ResMut<ArenaTurn>,
+    mut arena_state: ResMut<State<ArenaState>>,
+    q_combat: Query<&ArenaCombat>,
+    q_parent: Query<&Parent>,
+    mut q_deck: Query<&mut Deck>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    if turn.actor.is_some() {
+        return;
+    }
+    match arena_state.current() {
+        ArenaState::Inactive | ArenaState::Resolving => {}
+        _ => return,
+    }
+
+    if let Ok(combat) = q_combat.get_single() {
+        if is_over(combat, &order, &q_parent) {
+            return;
+        }
+        if let Some(actor) = order.queue.front().cloned() {
+            // Wait for the acting party's deck to be built
+            let party = match q_parent.get(actor) {
+                Ok(parent) => parent.0,
+                Err(_) => return,
+            };
+            let mut deck = match q_deck.get_mut(party) {
+                Ok(deck) => deck,
+                Err(_) => return,
+            };
+            deck.draw(HAND_SIZE, game_rng.stream(DECK_STREAM));
+
+            order.queue.rotate_left(1);
+
+            *turn = ArenaTurn {
+                actor: Some(actor),
+                ..Default::default()
+            };
+            commands.entity(actor).insert(TakingATurn);
+
+            if is_player_unit(combat, &q_parent, actor) {
+                let _ = arena_state.set(ArenaState::ChooseCard);
+            } else {
+                let _ = arena_state.set(ArenaState::EnemyTurn);
+            }
+        }
+    }
+}
+
+/// Whether either side has run out of units.
+fn is_over(combat: &ArenaCombat, order: &TurnOrder, q_parent: &Query<&Parent>) -> bool {
+    let players = order
+        .iter()
+        .filter(|unit| is_player_unit(combat, q_parent, **unit))
+        .count();
+    players == 0 || players == order.queue.len()
+}
+
+pub fn is_player_unit(combat: &ArenaCombat, q_parent: &Query<&Parent>, unit: Entity) -> bool {
+    q_parent
+        .get(unit)
+        .map(|p| p.0 == combat.player_party)
+        .unwrap_or(false)
+}
+
+/// Units that `card` may be played on when played by `actor`.
+pub fn valid_targets(
+    combat: &ArenaCombat,
+    order: &TurnOrder,
+    q_parent: &Query<&Parent>,
+    actor: Entity,
+    card: &CardAsset,
+) -> Vec<Entity> {
+    let actor_is_player = is_player_unit(combat, q_parent, actor);
+    order
+        .iter()
+        .filter(|unit| {
+            let same_side = is_player_unit(combat, q_parent, **unit) == actor_is_player;
+            same_side == card.targets_allies()
+        })
+        .cloned()
+        .collect()
+}
+
+fn choose_card(
+    mut egui: ResMut<EguiContext>,
+    mut turn: ResMut<ArenaTurn>,
+    mut arena_state: ResMut<State<ArenaState>>,
+    cards: Res<Assets<CardAsset>>,
+    q_name: Query<&Name>,
+    q_parent: Query<&Parent>,
+    q_deck: Query<&Deck>,
+) {
+    let actor = match turn.actor {
+        Some(actor) => actor,
+        None => return,
+    };
+    let name = q_name.get(actor).map(|n| n.as_str()).unwrap_or("Unit");
+    let hand = match q_parent.get(actor).and_then(|p| q_deck.get(p.0)) {
+        Ok(deck) => deck.hand(),
+        Err(_) => return,
+    };
+
+    let mut chosen = None;
+    egui::Window::new("Choose a card").show(egui.ctx_mut(), |ui| {
+        ui.label(format!("{}'s turn", name));
+        for path in hand.iter() {
+            if let Some(card) = cards.get(path.as_str()) {
+                if ui.button(&card.title).clicked() {
+                    chosen = Some(path.to_owned());
+                }
+            }
+        }
+    });
+
+    if let Some(card) = chosen {
+        turn.card = Some(card);
+        let _ = arena_state.set(ArenaState::SelectTarget);
+    }
+}
+
+/// A card dropped on a valid target skips straight to resolving it.
+fn play_card(
+    mut events: EventReader<CardPlayed>,
+    mut turn: ResMut<ArenaTurn>,
+    mut arena_state: ResMut<State<ArenaState>>,
+) {
+    for played in events.iter() {
+        match arena_state.current() {
+            ArenaState::ChooseCard | ArenaState::SelectTarget => {}
+            _ => return,
+        }
+        if turn.actor != Some(played.source) {
+            continue;
+        }
+        turn.card = Some(played.card.clone());
+        turn.target = Some(played.target);
+        let _ = arena_state.set(ArenaState::Resolving);
+        return;
+    }
+}
+
+/// Click a highlighted unit to play the chosen card on it. Escape or right click
+/// goes back to choosing a card.
+fn select_target(
+    input: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    mut turn: ResMut<ArenaTurn>,
+    mut arena_state: ResMut<State<ArenaState>>,
+    q_cam: Query<(&Camera, &GlobalTransform)>,
+    q_targets: Query<Entity, With<TargetHighlight>>,
+    q_unit: Query<&PartyUnit>,
+    q_sprite: Query<(&GlobalTransform, &TextureAtlasSprite)>,
+) {
+    if keys.just_pressed(KeyCode::Escape) || input.just_pressed(MouseButton::Right) {
+        turn.card = None;
+        let _ = arena_state.set(ArenaState::ChooseCard);
+        return;
+    }
+    if !input.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    if let (Ok((cam, cam_transform)), Some(window)) = (q_cam.get_single(), windows.get_primary()) {
+        if let Some(pos) = window.cursor_position() {
+            if let Some(pos) = screen_to_world(cam, &windows, cam_transform, pos) {
+                let targets: Vec<_> = q_targets.iter().collect();
+                if let Some(target) = unit_at(pos.truncate(), &targets, &q_unit, &q_sprite) {
+                    turn.target = Some(target);
+                    let _ = arena_state.set(ArenaState::Resolving);
+                }
+            }
+        }
+    }
+}
+
+/// The unit in `units` whose arena sprite is under `point`.
+pub fn unit_at(
+    point: Vec2,
+    units: &[Entity],
+    q_unit: &Query<&PartyUnit>,
+    q_sprite: &Query<(&GlobalTransform, &TextureAtlasSprite)>,
+) -> Option<Entity> {
+    units.iter().cloned().find(|unit| {
+        q_unit
+            .get(*unit)
+            .and_then(|unit| q_sprite.get(unit.arena_sprite()))
+            .map_or(false, |(transform, sprite)| {
+                let size = sprite.custom_size.unwrap_or(Vec2::splat(TILE_SIZE as f32));
+                let d = (point - transform.translation.truncate()).abs();
+                d.x <= size.x / 2.0 && d.y <= size.y / 2.0
+            })
+    })
+}
+
+/// Tint the arena sprites of units the chosen card can be played on.
+fn highlight_targets(
+    mut commands: Commands,
+    turn: Res<ArenaTurn>,
+    order: Res<TurnOrder>,
+    cards: Res<Assets<CardAsset>>,
+    q_combat: Query<&ArenaCombat>,
+    q_parent: Query<&Parent>,
+    q_unit: Query<&PartyUnit>,
+    mut q_sprite: Query<&mut TextureAtlasSprite>,
+) {
+    let (actor, card) = match (turn.actor, turn.card.as_ref()) {
+        (Some(actor), Some(card)) => (actor, card),
+        _ => return,
+    };
+    let card = match cards.get(card.as_str()) {
+        Some(card) => card,
+        None => return,
+    };
+
+    let tint = match card.targets_allies() {
+        true => ALLY_TARGET_TINT,
+        false => ENEMY_TARGET_TINT,
+    };
+    if let Ok(combat) = q_combat.get_single() {
+        for target in valid_targets(combat, &order, &q_parent, actor, card) {
+            if let Ok(unit) = q_unit.get(target) {
+                if let Ok(mut sprite) = q_sprite.get_mut(unit.arena_sprite()) {
+                    sprite.color = tint;
+                }
+                commands.entity(target).insert(TargetHighlight);
+            }
+        }
     }
 }
 
+fn clear_highlights(
+    mut commands: Commands,
+    q_targets: Query<(Entity, &PartyUnit), With<TargetHighlight>>,
+    mut q_sprite: Query<&mut TextureAtlasSprite>,
+) {
+    for (entity, unit) in q_targets.iter() {
+        if let Ok(mut sprite) = q_sprite.get_mut(unit.arena_sprite()) {
+            sprite.color = Color::WHITE;
+        }
+        commands.entity(entity).remove::<TargetHighlight>();
+    }
+}
+
+fn enemy_turn(
+    time: Res<Time>,
+    mut think: Local<Timer>,
+    mut turn: ResMut<ArenaTurn>,
+    mut arena_state: ResMut<State<ArenaState>>,
+    order: Res<TurnOrder>,
+    cards: Res<Assets<CardAsset>>,
+    q_combat: Query<&ArenaCombat>,
+    q_parent: Query<&Parent>,
+    q_deck: Query<&Deck>,
+    q_units: Query<(&Stats, &Shields, Option<&AiStrategy>)>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    let actor = match turn.actor {
+        Some(actor) => actor,
+        None => return,
+    };
+
+    // Give the player a moment to see whose turn it is
+    if think.duration().as_secs_f32() == 0.0 {
+        *think = Timer::from_seconds(1.0, false);
+    }
+    if !think.tick(time.delta()).finished() {
+        return;
+    }
+    think.reset();
+
+    if let Ok(combat) = q_combat.get_single() {
+        let rng = game_rng.stream(ARENA_AI_STREAM);
+        let hand = match q_parent.get(actor).and_then(|p| q_deck.get(p.0)) {
+            Ok(deck) => deck.hand(),
+            Err(_) => return,
+        };
+        let (stats, strategy) = match q_units.get(actor) {
+            Ok((stats, _, strategy)) => (stats, strategy.cloned().unwrap_or_default()),
+            Err(_) => return,
+        };
+
+        let (paths, hand): (Vec<_>, Vec<_>) = hand
+            .iter()
+            .filter_map(|path| cards.get(path.as_str()).map(|card| (path, card)))
+            .unzip();
+        let actor_is_player = is_player_unit(combat, &q_parent, actor);
+        let targets: Vec<_> = order
+            .iter()
+            .filter(|unit| is_player_unit(combat, &q_parent, **unit) != actor_is_player)
+            .filter_map(|unit| {
+                q_units.get(*unit).ok().map(|(stats, shields, _)| AiTarget {
+                    entity: *unit,
+                    stats,
+                    shields,
+                })
+            })
+            .collect();
+
+        if let Some((card, target)) = choose_play(strategy, stats, &hand, &targets, rng) {
+            turn.card = Some(paths[card].to_owned());
+            turn.target = Some(target);
+        }
+        let _ = arena_state.set(ArenaState::Resolving);
+    }
+}
+
+/// Play the actor's attack script, apply the card when the script reaches its
+/// resolve marker, then end the turn once the script is done. Units without a
+/// script resolve their card straight away.
 fn do_turn(
     mut commands: Commands,
-    mut q_units: Query<(Entity, &mut Animator, &Transform), With<TakingATurn>>,
+    mut turn: ResMut<ArenaTurn>,
+    mut markers: EventReader<AnimationMarker>,
+    arena_state: Res<State<ArenaState>>,
+    q_units: Query<&PartyUnit, With<TakingATurn>>,
+    mut q_animator: Query<(&mut Animator, &AttackActions)>,
+    mut q_stats: Query<(&mut Stats, &mut Shields)>,
+    cards: Res<Assets<CardAsset>>,
+    q_name: Query<&Name>,
+    q_parent: Query<&Parent>,
+    q_combat: Query<&ArenaCombat>,
+    mut q_deck: Query<&mut Deck>,
+    mut results: ResMut<CombatResults>,
 ) {
-    for (entity, mut driver, transform) in q_units.iter_mut() {
-        //println!("TAKING A TURN");
-        //commands.entity(entity).remove::<TakingATurn>();
-        //driver.add_wait(3.0).add_move(-5.0, -3.0, 3.0).add_wait(3.0).add_move(3.5, 0.0, 5.0);
+    let markers: Vec<_> = markers
+        .iter()
+        .filter(|m| m.marker == RESOLVE_MARKER)
+        .map(|m| m.entity)
+        .collect();
+
+    if arena_state.current() != &ArenaState::Resolving {
+        return;
+    }
+    let actor = match turn.actor {
+        Some(actor) => actor,
+        None => return,
+    };
+    let sprite = match q_units.get(actor) {
+        Ok(unit) => unit.arena_sprite(),
+        Err(_) => return,
+    };
+    let by_player = q_combat
+        .get_single()
+        .map_or(false, |combat| is_player_unit(combat, &q_parent, actor));
+    let terrain = q_combat.get_single().map(|combat| combat.terrain).unwrap_or_default();
+    let modifiers = TerrainModifiers::new(terrain);
+
+    if !turn.animating {
+        turn.animating = true;
+        if let Ok((mut animator, actions)) = q_animator.get_mut(sprite) {
+            animator.push_cmds_back(actions.0.iter().cloned());
+        }
+    }
+
+    if !turn.resolved && markers.contains(&sprite) {
+        let damage = resolve_card(&turn, &cards, &q_name, &modifiers, &mut q_stats);
+        results.record_damage(by_player, damage);
+        turn.resolved = true;
+    }
+
+    let idle = q_animator.get(sprite).map_or(true, |(animator, _)| animator.is_idle());
+    if !idle {
+        return;
+    }
+
+    if !turn.resolved {
+        let damage = resolve_card(&turn, &cards, &q_name, &modifiers, &mut q_stats);
+        results.record_damage(by_player, damage);
+    }
+
+    if let Ok(parent) = q_parent.get(actor) {
+        if let Ok(mut deck) = q_deck.get_mut(parent.0) {
+            if let Some(card) = &turn.card {
+                deck.discard(card);
+            }
+            deck.discard_hand();
+        }
+    }
+
+    commands.entity(actor).remove::<TakingATurn>();
+    *turn = ArenaTurn::default();
+}
+
+fn resolve_card(
+    turn: &ArenaTurn,
+    cards: &Assets<CardAsset>,
+    q_name: &Query<&Name>,
+    modifiers: &TerrainModifiers,
+    q_stats: &mut Query<(&mut Stats, &mut Shields)>,
+) -> i32 {
+    let card = turn.card.as_ref().and_then(|path| cards.get(path.as_str()));
+    if let (Some(actor), Some(card), Some(target)) = (turn.actor, card, turn.target) {
+        let name = |e: Entity| q_name.get(e).map(|n| n.as_str().to_owned()).unwrap_or_default();
+        info!("{} plays {} on {}", name(actor), card.title, name(target));
+        return card
+            .effects
+            .iter()
+            .map(|effect| resolve_effect(actor, target, effect, modifiers, q_stats))
+            .sum();
+    }
+    0
+}
+
+fn resolve_effect(
+    actor: Entity,
+    target: Entity,
+    effect: &CardEffect,
+    modifiers: &TerrainModifiers,
+    q_stats: &mut Query<(&mut Stats, &mut Shields)>,
+) -> i32 {
+    let attacker = match q_stats.get(actor) {
+        Ok((stats, _)) => stats.clone(),
+        Err(_) => return 0,
+    };
+    match effect {
+        CardEffect::Damage(damage) => {
+            if let Ok((mut defender, _)) = q_stats.get_mut(target) {
+                let damage = modifiers.physical_damage(*damage, &attacker, &defender);
+                defender.take_damage(damage);
+                return damage;
+            }
+        }
+        CardEffect::Heal(amount) => {
+            if let Ok((mut stats, _)) = q_stats.get_mut(target) {
+                stats.heal(*amount);
+            }
+        }
+        CardEffect::ElementalDamage { element, damage } => {
+            if let Ok((mut defender, mut shields)) = q_stats.get_mut(target) {
+                let shielded = shields.absorb(*element);
+                let damage =
+                    modifiers.elemental_damage(*damage, *element, &attacker, &defender, shielded);
+                defender.take_damage(damage);
+                return damage;
+            }
+        }
+        CardEffect::ElementalShield { element } => {
+            if let Ok((_, mut shields)) = q_stats.get_mut(target) {
+                shields.add(*element);
+            }
+        }
+    }
+    0
+}
+
+/// Dead units leave the turn order and are despawned along with their sprites,
+/// which also removes them from their party.
+fn remove_dead_units(
+    mut commands: Commands,
+    mut order: ResMut<TurnOrder>,
+    mut results: ResMut<CombatResults>,
+    q_combat: Query<&ArenaCombat>,
+    q_parent: Query<&Parent>,
+    q_units: Query<(Entity, &Stats, &Name), (With<PartyUnit>, Changed<Stats>)>,
+) {
+    for (entity, stats, name) in q_units.iter() {
+        if stats.is_dead() {
+            let player = q_combat
+                .get_single()
+                .map_or(false, |combat| is_player_unit(combat, &q_parent, entity));
+            match player {
+                true => results.units_lost.push(name.as_str().to_owned()),
+                false => results.enemies_defeated.push(name.as_str().to_owned()),
+            }
+            order.remove(entity);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Once a side has no units left and the last turn has played out, the losing
+/// party is removed from the map, the winners' arena sprites are hidden and
+/// the results of the fight are shown.
+fn end_combat(
+    mut commands: Commands,
+    mut state: ResMut<State<GameState>>,
+    mut arena_state: ResMut<State<ArenaState>>,
+    mut order: ResMut<TurnOrder>,
+    mut turn: ResMut<ArenaTurn>,
+    mut results: ResMut<CombatResults>,
+    q_combat: Query<(Entity, &ArenaCombat)>,
+    q_parent: Query<&Parent>,
+    q_children: Query<&Children>,
+    mut q_units: Query<&mut Shields, With<PartyUnit>>,
+    q_arena: Query<Entity, With<ArenaEntity>>,
+) {
+    if turn.actor.is_some() {
+        return;
+    }
+    let (combat_entity, combat) = match q_combat.get_single() {
+        Ok(combat) => combat,
+        Err(_) => return,
+    };
+    if !is_over(combat, &order, &q_parent) {
+        return;
+    }
+
+    let player_won = order
+        .iter()
+        .any(|unit| is_player_unit(combat, &q_parent, *unit));
+    let (winner, loser) = match player_won {
+        true => (combat.player_party, combat.enemy_party),
+        false => (combat.enemy_party, combat.player_party),
+    };
+    info!("Combat over, {} won", if player_won { "player" } else { "enemy" });
+    results.player_won = player_won;
+    results.player_party = player_won.then(|| combat.player_party);
+
+    commands.entity(loser).despawn_recursive();
+    if let Ok(children) = q_children.get(winner) {
+        for unit in children.iter() {
+            if let Ok(mut shields) = q_units.get_mut(*unit) {
+                shields.clear();
+                commands.entity(*unit).insert(ArenaSpriteVisibility(false));
+            }
+        }
+    }
+
+    commands.entity(combat_entity).despawn();
+    for entity in q_arena.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    order.queue.clear();
+    *turn = ArenaTurn::default();
+    // A turn system may have queued a transition this frame too, but the
+    // fight is over either way
+    if arena_state.current() != &ArenaState::Inactive {
+        let _ = arena_state.overwrite_set(ArenaState::Inactive);
     }
+    let _ = state.set(GameState::ArenaResults);
 }
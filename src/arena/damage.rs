@@ -0,0 +1,74 @@
+use crate::unit::{physical_damage, Element, Stats};
+
+/// Damage dealt by an elemental attack of the given `power`.
+///
+/// Starts from [physical_damage], then the attacker's affinity boosts it and the
+/// defender's resistance reduces it if their elements match the attack. A shielded
+/// defender takes no damage at all.
+pub fn elemental_damage(
+    power: i32,
+    element: Element,
+    attacker: &Stats,
+    defender: &Stats,
+    shielded: bool,
+) -> i32 {
+    if shielded {
+        return 0;
+    }
+
+    let mut damage = physical_damage(power, attacker, defender) as f32;
+
+    if let Some(affinity) = attacker.affinity() {
+        if affinity.element() == element {
+            damage *= 1.0 + affinity.value();
+        }
+    }
+
+    if let Some(resistance) = defender.resistance() {
+        if resistance.element() == element {
+            damage *= (1.0 - resistance.value()).max(0.0);
+        }
+    }
+
+    damage.round() as i32
+}
+
+#[cfg(test)]
+use crate::unit::{Affinity, Resistance};
+
+#[test]
+fn no_elements() {
+    let attacker = Stats::new(10, 2, 0);
+    let defender = Stats::new(10, 0, 1);
+    assert_eq!(elemental_damage(4, Element::Fire, &attacker, &defender, false), 5);
+}
+
+#[test]
+fn affinity_boosts_matching_element() {
+    let attacker = Stats::new(10, 0, 0).with_affinity(Affinity::new(Element::Fire, 0.5));
+    let defender = Stats::new(10, 0, 0);
+    assert_eq!(elemental_damage(4, Element::Fire, &attacker, &defender, false), 6);
+    assert_eq!(elemental_damage(4, Element::Holy, &attacker, &defender, false), 4);
+}
+
+#[test]
+fn resistance_cuts_matching_element() {
+    let attacker = Stats::new(10, 0, 0);
+    let defender = Stats::new(10, 0, 0).with_resistance(Resistance::new(Element::Death, 0.75));
+    assert_eq!(elemental_damage(8, Element::Death, &attacker, &defender, false), 2);
+    assert_eq!(elemental_damage(8, Element::Lightning, &attacker, &defender, false), 8);
+}
+
+#[test]
+fn full_resistance_is_immune() {
+    let attacker = Stats::new(10, 5, 0);
+    let defender = Stats::new(10, 0, 0).with_resistance(Resistance::new(Element::Holy, 1.5));
+    assert_eq!(elemental_damage(8, Element::Holy, &attacker, &defender, false), 0);
+}
+
+#[test]
+fn shield_absorbs_everything() {
+    let attacker = Stats::new(10, 5, 0).with_affinity(Affinity::new(Element::Fire, 1.0));
+    let defender = Stats::new(10, 0, 0);
+    assert_eq!(elemental_damage(8, Element::Fire, &attacker, &defender, true), 0);
+}
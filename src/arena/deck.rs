@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use rand::{prelude::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ConfigAsset,
+    rng::{GameRng, DECK_STREAM},
+    unit::UnitCards,
+    GameState, SETTINGS_PATH,
+};
+
+use super::ArenaCombat;
+
+/// How many cards a party draws at the start of each of its units' turns.
+pub const HAND_SIZE: usize = 5;
+
+pub struct DeckPlugin;
+
+impl Plugin for DeckPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Arena).with_system(build_decks));
+    }
+}
+
+/// Cards a party has won in fights, added to its deck on top of its units' cards.
+#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct EarnedCards(pub Vec<String>);
+
+/// A party's cards during a fight. Cards are referred to by their asset path.
+#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Deck {
+    draw_pile: Vec<String>,
+    hand: Vec<String>,
+    discard_pile: Vec<String>,
+}
+
+impl Deck {
+    pub fn new(cards: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            draw_pile: cards.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn shuffle(&mut self, rng: &mut impl Rng) {
+        self.draw_pile.shuffle(rng);
+    }
+
+    /// Draw up to `count` cards into the hand. The discard pile is shuffled back
+    /// into the draw pile when it runs out. Returns how many cards were drawn.
+    pub fn draw(&mut self, count: usize, rng: &mut impl Rng) -> usize {
+        let mut drawn = 0;
+        while drawn < count {
+            if self.draw_pile.is_empty() {
+                if self.discard_pile.is_empty() {
+                    break;
+                }
+                self.reshuffle_discard(rng);
+            }
+            if let Some(card) = self.draw_pile.pop() {
+                self.hand.push(card);
+                drawn += 1;
+            }
+        }
+        drawn
+    }
+
+    /// Move a card from the hand to the discard pile. Returns false if the card
+    /// wasn't in the hand.
+    pub fn discard(&mut self, card: &str) -> bool {
+        if let Some(i) = self.hand.iter().position(|c| c == card) {
+            let card = self.hand.remove(i);
+            self.discard_pile.push(card);
+            return true;
+        }
+        false
+    }
+
+    pub fn discard_hand(&mut self) {
+        self.discard_pile.append(&mut self.hand);
+    }
+
+    /// Shuffle the discard pile back into the draw pile.
+    pub fn reshuffle_discard(&mut self, rng: &mut impl Rng) {
+        self.draw_pile.append(&mut self.discard_pile);
+        self.shuffle(rng);
+    }
+
+    pub fn hand(&self) -> &[String] {
+        &self.hand
+    }
+
+    pub fn draw_pile(&self) -> &[String] {
+        &self.draw_pile
+    }
+
+    pub fn discard_pile(&self) -> &[String] {
+        &self.discard_pile
+    }
+}
+
+/// Give each party in the fight a fresh deck made from its units' cards.
+fn build_decks(
+    mut commands: Commands,
+    config: Res<Assets<ConfigAsset>>,
+    q_combat: Query<&ArenaCombat>,
+    q_children: Query<&Children>,
+    q_cards: Query<&UnitCards>,
+    q_earned: Query<&EarnedCards>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    if let Some(config) = config.get(SETTINGS_PATH) {
+        if let Ok(combat) = q_combat.get_single() {
+            let rng = game_rng.stream(DECK_STREAM);
+            for party in [combat.player_party, combat.enemy_party] {
+                let mut cards = Vec::new();
+                if let Ok(children) = q_children.get(party) {
+                    for unit in children.iter() {
+                        if let Ok(unit_cards) = q_cards.get(*unit) {
+                            if unit_cards.0.is_empty() {
+                                cards.extend(config.settings.starter_cards.iter().cloned());
+                            } else {
+                                cards.extend(unit_cards.0.iter().cloned());
+                            }
+                        }
+                    }
+                }
+                if let Ok(earned) = q_earned.get(party) {
+                    cards.extend(earned.0.iter().cloned());
+                }
+                let mut deck = Deck::new(cards);
+                deck.shuffle(rng);
+                commands.entity(party).insert(deck);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+#[cfg(test)]
+fn test_deck() -> Deck {
+    Deck::new(["a", "b", "c", "d", "e"].iter().map(|s| s.to_string()))
+}
+
+#[test]
+fn draw() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = test_deck();
+    assert_eq!(deck.draw(3, &mut rng), 3);
+    assert_eq!(deck.hand().len(), 3);
+    assert_eq!(deck.draw_pile().len(), 2);
+}
+
+#[test]
+fn discard() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = test_deck();
+    deck.draw(2, &mut rng);
+    let card = deck.hand()[0].clone();
+    assert!(deck.discard(&card));
+    assert!(!deck.discard("not in hand"));
+    assert_eq!(deck.discard_pile(), &[card]);
+
+    deck.discard_hand();
+    assert!(deck.hand().is_empty());
+    assert_eq!(deck.discard_pile().len(), 2);
+}
+
+#[test]
+fn draw_reshuffles_discard() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = test_deck();
+    deck.draw(5, &mut rng);
+    deck.discard_hand();
+    assert!(deck.draw_pile().is_empty());
+
+    assert_eq!(deck.draw(3, &mut rng), 3);
+    assert_eq!(deck.draw_pile().len(), 2);
+    assert!(deck.discard_pile().is_empty());
+}
+
+#[test]
+fn draw_stops_when_out_of_cards() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = test_deck();
+    assert_eq!(deck.draw(10, &mut rng), 5);
+}
+
+#[test]
+fn seeded_shuffle_is_deterministic() {
+    let mut a = test_deck();
+    let mut b = test_deck();
+    a.shuffle(&mut StdRng::seed_from_u64(42));
+    b.shuffle(&mut StdRng::seed_from_u64(42));
+    assert_eq!(a.draw_pile(), b.draw_pile());
+}
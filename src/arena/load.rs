@@ -7,10 +7,10 @@ use crate::{
     //prefab::ChangeSprite, 
     unit::{Element, Player, Enemy}, AtlasHandles, GameState,
     //LoadCardPrefab, SpawnPrefabOld, 
-    SETTINGS_PATH, TILE_SIZE, animation::{Animator, AnimationCommand}, make_spritesheet_bundle, party::{PartyUnit, GenerateParty, Party, PartyUnitSprite}, GENERATE_PARTY_SYSTEM,
+    SETTINGS_PATH, TILE_SIZE, animation::{Animator, AnimationCommand}, make_spritesheet_bundle, battle_map::TerrainTile, party::{PartyUnit, GenerateParty, Party, PartyUnitSprite}, GENERATE_PARTY_SYSTEM,
 };
 
-use super::{cards::{CardLabel, CardLabelType, CardsAtlas, SpawnCard}, TakingATurn, ArenaCombat};
+use super::{cards::{CardLayout, CardsAtlas}, combat::AttackActions, terrain::arena_background, ArenaEntity, TakingATurn, ArenaCombat};
 
 pub struct ArenaLoadPlugin;
 
@@ -66,6 +66,7 @@ fn setup(
     mut commands: Commands,
     mut state: ResMut<State<GameState>>,
     mut card_atlas: ResMut<CardsAtlas>,
+    asset_server: Res<AssetServer>,
     ldtk: Res<Assets<LdtkMap>>,
     config: Res<Assets<ConfigAsset>>,
     q_combat: Query<&ArenaCombat>,
@@ -90,24 +91,32 @@ fn setup(
             commands.spawn().insert(ArenaCombat {
                 player_party,
                 enemy_party,
+                terrain: TerrainTile::default(),
             });
             // Let commands execute
             return;
         }
 
-        if let Some(bg) = &ldtk.background() {
-            let mut pos = q_cam.single().translation;
-            pos.z = 10.0;
-            commands.spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    custom_size: Some(ldtk.size_px().as_vec2()),
-                    ..Default::default()
-                },
-                texture: bg.image.clone(),
-                transform: Transform::from_translation(pos),
-                ..Default::default()
-            });
+        match ldtk.tileset_from_name("battle_cards") {
+            Some(tileset) => card_atlas.0 = tileset.atlas().clone(),
+            None => warn!("Couldn't find 'Battle_Cards' tileset in {}", ldtk.name()),
         }
+        commands.insert_resource(CardLayout::from_ldtk(ldtk));
+
+        // The background shows the terrain the fight broke out on
+        let terrain = q_combat.get_single().map(|c| c.terrain).unwrap_or_default();
+        let mut pos = q_cam.single().translation;
+        pos.z = 10.0;
+        commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(ldtk.size_px().as_vec2()),
+                ..Default::default()
+            },
+            texture: asset_server.load(arena_background(terrain)),
+            transform: Transform::from_translation(pos),
+            ..Default::default()
+        })
+        .insert(ArenaEntity);
 
 
         if let Ok(combat) = q_combat.get_single() {
@@ -115,8 +124,6 @@ fn setup(
             let spawns: Vec<_> = ldtk.get_tagged("spawn_point").collect();
             let player_spawns = spawns.iter().filter(|e|e.tags().has("player") && !e.tags().has("card"));
     
-            let actions = player_spawns.clone().map(|e|try_get_actions(e.fields(), "attackactions"));
-    
             let player_units = q_units.get(combat.player_party).unwrap();
             for (i,(spawn,unit)) in player_spawns.zip(player_units.iter()).enumerate() {
                 let unit = q_unit.get(*unit).unwrap();
@@ -127,12 +134,14 @@ fn setup(
     
                 transform.translation = local_pos;
                 visibility.is_visible = true;
+
+                commands.entity(sprite_entity)
+                    .insert(Animator::default())
+                    .insert(AttackActions(spawn_actions(spawn)));
             }
     
             let enemy_spawns = spawns.iter().filter(|e|e.tags().has("enemy") && !e.tags().has("card"));
     
-            let actions = enemy_spawns.clone().map(|e|try_get_actions(e.fields(), "attackactions"));
-    
             let enemy_units = q_units.get(combat.enemy_party).unwrap();
             for (i,(spawn,unit)) in enemy_spawns.rev().zip(enemy_units.iter()).enumerate() {
                 let unit = q_unit.get(*unit).unwrap();
@@ -143,6 +152,10 @@ fn setup(
     
                 transform.translation = local_pos;
                 visibility.is_visible = true;
+
+                commands.entity(sprite_entity)
+                    .insert(Animator::default())
+                    .insert(AttackActions(spawn_actions(spawn)));
             }
 
             commands.spawn_bundle(SpriteBundle {
@@ -152,7 +165,8 @@ fn setup(
                 },
                 transform: Transform::from_xyz(50.0,50.0,30.0),
                 ..Default::default()
-            });
+            })
+            .insert(ArenaEntity);
     
         }
 
@@ -277,10 +291,12 @@ fn try_get_actions<'a>(
     None
 } 
 
-fn play_turn(
-
-) {
-    
+/// The attack script on a unit spawn point. Enemy and player spawn points
+/// name the field differently.
+fn spawn_actions(spawn: &PrefabEntity) -> Vec<AnimationCommand> {
+    try_get_actions(spawn.fields(), "attackactions")
+        .or_else(|| try_get_actions(spawn.fields(), "attack_actions"))
+        .unwrap_or_default()
 }
 
 fn get_atlas(
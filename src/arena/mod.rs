@@ -1,17 +1,30 @@
+pub mod ai;
+pub mod card_asset;
 pub mod cards;
 mod combat;
+mod damage;
+pub mod deck;
 mod load;
+pub mod results;
+pub mod terrain;
 mod units;
 
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::GameState;
+use crate::{battle_map::TerrainTile, GameState};
 
-use self::{cards::CardsPlugin, combat::CombatPlugin, load::ArenaLoadPlugin};
+use self::{
+    cards::CardsPlugin, combat::CombatPlugin, deck::DeckPlugin, load::ArenaLoadPlugin,
+    results::ResultsPlugin,
+};
 #[derive(Component)]
 pub struct TakingATurn;
 
+/// Anything spawned for the arena scene, despawned when combat ends.
+#[derive(Component)]
+pub struct ArenaEntity;
+
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
@@ -20,8 +33,10 @@ impl Plugin for ArenaPlugin {
         app.add_state(ArenaState::Inactive)
             //.add_system_set(SystemSet::on_enter(ArenaState::Loading).with_system(on_enter))
             .add_plugin(CombatPlugin)
+            .add_plugin(DeckPlugin)
             .add_plugin(ArenaLoadPlugin)
-            .add_plugin(CardsPlugin);
+            .add_plugin(CardsPlugin)
+            .add_plugin(ResultsPlugin);
     }
 }
 
@@ -29,6 +44,8 @@ impl Plugin for ArenaPlugin {
 pub struct ArenaCombat {
     pub player_party: Entity,
     pub enemy_party: Entity,
+    /// The battle map terrain the fight broke out on.
+    pub terrain: TerrainTile,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -38,4 +55,5 @@ pub enum ArenaState {
     ChooseCard,
     SelectTarget,
     EnemyTurn,
+    Resolving,
 }
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+use rand::{prelude::SliceRandom, Rng};
+
+use crate::{
+    config::ConfigAsset,
+    rng::{GameRng, REWARD_STREAM},
+    GameState, SETTINGS_PATH,
+};
+
+use super::{
+    card_asset::{CardAsset, Rarity},
+    deck::EarnedCards,
+};
+
+/// How many cards the player gets to choose from after a win.
+pub const REWARD_CHOICES: usize = 3;
+
+pub struct ResultsPlugin;
+
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatResults>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::ArenaResults).with_system(offer_rewards),
+            )
+            .add_system_set(SystemSet::on_update(GameState::ArenaResults).with_system(gui));
+    }
+}
+
+/// What happened during the last fight, filled in as the fight plays out.
+#[derive(Default, Debug)]
+pub struct CombatResults {
+    pub player_won: bool,
+    pub player_party: Option<Entity>,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub units_lost: Vec<String>,
+    pub enemies_defeated: Vec<String>,
+    /// Cards offered as a reward, by asset path.
+    pub rewards: Vec<String>,
+    pub earned: Option<String>,
+}
+
+impl CombatResults {
+    pub fn record_damage(&mut self, by_player: bool, damage: i32) {
+        match by_player {
+            true => self.damage_dealt += damage,
+            false => self.damage_taken += damage,
+        }
+    }
+}
+
+/// Pick up to `count` different cards from `pool`, rarer cards being less likely.
+pub fn roll_rewards(pool: &[(&str, Rarity)], count: usize, rng: &mut impl Rng) -> Vec<String> {
+    match pool.choose_multiple_weighted(rng, count, |(_, rarity)| rarity.weight()) {
+        Ok(cards) => cards.map(|(path, _)| path.to_string()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn offer_rewards(
+    mut results: ResMut<CombatResults>,
+    config: Res<Assets<ConfigAsset>>,
+    cards: Res<Assets<CardAsset>>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    results.rewards.clear();
+    results.earned = None;
+    if !results.player_won {
+        return;
+    }
+    if let Some(config) = config.get(SETTINGS_PATH) {
+        let pool: Vec<_> = config
+            .settings
+            .cards
+            .iter()
+            .filter_map(|path| cards.get(path.as_str()).map(|card| (path.as_str(), card.rarity)))
+            .collect();
+        results.rewards = roll_rewards(&pool, REWARD_CHOICES, game_rng.stream(REWARD_STREAM));
+    }
+}
+
+fn gui(
+    mut commands: Commands,
+    mut egui: ResMut<EguiContext>,
+    mut results: ResMut<CombatResults>,
+    mut state: ResMut<State<GameState>>,
+    cards: Res<Assets<CardAsset>>,
+    mut q_earned: Query<&mut EarnedCards>,
+) {
+    let ctx = egui.ctx_mut();
+
+    let mut chosen = None;
+    let mut done = false;
+    egui::panel::SidePanel::left("results").show(ctx, |ui| {
+        ui.heading(if results.player_won { "Victory!" } else { "Defeat" });
+        ui.separator();
+
+        ui.label(format!("Damage dealt: {}", results.damage_dealt));
+        ui.label(format!("Damage taken: {}", results.damage_taken));
+        ui.label(format!("Enemies defeated: {}", results.enemies_defeated.len()));
+        ui.label("Units lost:");
+        if results.units_lost.is_empty() {
+            ui.label("  None");
+        }
+        for name in results.units_lost.iter() {
+            ui.label(format!("  {}", name));
+        }
+
+        if results.player_won {
+            ui.separator();
+            match &results.earned {
+                Some(path) => {
+                    let title = cards.get(path.as_str()).map_or(path.as_str(), |c| &c.title);
+                    ui.label(format!("Cards earned: {}", title));
+                }
+                None => {
+                    ui.label("Choose a card:");
+                    for path in results.rewards.iter() {
+                        if let Some(card) = cards.get(path.as_str()) {
+                            let text = format!("{} ({:?})", card.title, card.rarity);
+                            if ui.button(text).clicked() {
+                                chosen = Some(path.to_owned());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        ui.separator();
+        done = ui.button("Continue").clicked();
+    });
+
+    if let Some(card) = chosen {
+        if let Some(party) = results.player_party {
+            match q_earned.get_mut(party) {
+                Ok(mut earned) => earned.0.push(card.clone()),
+                Err(_) => {
+                    commands.entity(party).insert(EarnedCards(vec![card.clone()]));
+                }
+            }
+        }
+        results.earned = Some(card);
+    }
+
+    if done {
+        state.set(GameState::BattleMap).unwrap();
+    }
+}
+
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn rewards_are_distinct() {
+    let pool = [
+        ("a", Rarity::Common),
+        ("b", Rarity::Common),
+        ("c", Rarity::Uncommon),
+        ("d", Rarity::Rare),
+    ];
+    let mut rewards = roll_rewards(&pool, 3, &mut StdRng::seed_from_u64(3));
+    assert_eq!(rewards.len(), 3);
+    rewards.sort();
+    rewards.dedup();
+    assert_eq!(rewards.len(), 3);
+
+    assert_eq!(roll_rewards(&pool, 10, &mut StdRng::seed_from_u64(3)).len(), 4);
+}
+
+#[test]
+fn rares_are_rare() {
+    let pool = [("common", Rarity::Common), ("rare", Rarity::Rare)];
+    let mut rng = StdRng::seed_from_u64(0);
+    let rares = (0..1000)
+        .filter(|_| roll_rewards(&pool, 1, &mut rng)[0] == "rare")
+        .count();
+    assert!(rares > 0 && rares < 300);
+}
@@ -0,0 +1,91 @@
+use crate::{
+    battle_map::TerrainTile,
+    unit::{physical_damage, Element, Stats},
+};
+
+use super::damage::elemental_damage;
+
+/// How the ground a fight happens on changes it. Both sides fight on the same
+/// tile, so every unit gets the same modifiers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainModifiers {
+    /// Added to the defense of every unit that gets hit.
+    pub defense: i32,
+    /// Elemental damage of this element is scaled by the given amount.
+    pub element: Option<(Element, f32)>,
+}
+
+impl TerrainModifiers {
+    pub fn new(terrain: TerrainTile) -> Self {
+        let (defense, element) = match terrain {
+            TerrainTile::Mountain => (1, None),
+            TerrainTile::Water => (0, Some((Element::Fire, 0.5))),
+            TerrainTile::Mud => (0, Some((Element::Lightning, 1.5))),
+            TerrainTile::Dirt | TerrainTile::Grass => (0, None),
+        };
+        Self { defense, element }
+    }
+
+    fn defender(&self, defender: &Stats) -> Stats {
+        defender.clone().with_bonus_defense(self.defense)
+    }
+
+    pub fn physical_damage(&self, power: i32, attacker: &Stats, defender: &Stats) -> i32 {
+        physical_damage(power, attacker, &self.defender(defender))
+    }
+
+    pub fn elemental_damage(
+        &self,
+        power: i32,
+        element: Element,
+        attacker: &Stats,
+        defender: &Stats,
+        shielded: bool,
+    ) -> i32 {
+        let damage = elemental_damage(power, element, attacker, &self.defender(defender), shielded);
+        match self.element {
+            Some((e, scale)) if e == element => (damage as f32 * scale).round() as i32,
+            _ => damage,
+        }
+    }
+}
+
+/// The arena background for a fight on `terrain`.
+pub fn arena_background(terrain: TerrainTile) -> &'static str {
+    match terrain {
+        TerrainTile::Water => "textures/BattleArenaBackground_02.png",
+        _ => "textures/BattleArenaBackground_01.png",
+    }
+}
+
+#[test]
+fn mountains_defend() {
+    let attacker = Stats::new(10, 2, 0);
+    let defender = Stats::new(10, 0, 1);
+    let mountain = TerrainModifiers::new(TerrainTile::Mountain);
+    assert_eq!(mountain.physical_damage(4, &attacker, &defender), 4);
+    assert_eq!(mountain.elemental_damage(4, Element::Holy, &attacker, &defender, false), 4);
+    assert_eq!(TerrainModifiers::new(TerrainTile::Grass).physical_damage(4, &attacker, &defender), 5);
+}
+
+#[test]
+fn water_douses_fire() {
+    let attacker = Stats::new(10, 0, 0);
+    let defender = Stats::new(10, 0, 0);
+    let water = TerrainModifiers::new(TerrainTile::Water);
+    assert_eq!(water.elemental_damage(8, Element::Fire, &attacker, &defender, false), 4);
+    assert_eq!(water.elemental_damage(8, Element::Lightning, &attacker, &defender, false), 8);
+    assert_eq!(water.elemental_damage(8, Element::Fire, &attacker, &defender, true), 0);
+}
+
+#[test]
+fn mud_conducts_lightning() {
+    let attacker = Stats::new(10, 0, 0);
+    let defender = Stats::new(10, 0, 0);
+    let mud = TerrainModifiers::new(TerrainTile::Mud);
+    assert_eq!(mud.elemental_damage(4, Element::Lightning, &attacker, &defender, false), 6);
+    assert_eq!(mud.physical_damage(4, &attacker, &defender), 4);
+
+    assert_eq!(arena_background(TerrainTile::Water), "textures/BattleArenaBackground_02.png");
+    assert_eq!(arena_background(TerrainTile::Mud), "textures/BattleArenaBackground_01.png");
+}
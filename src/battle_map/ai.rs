@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+
+use crate::ldtk_loader::Fields;
+
+/// How close a player party has to be before a weak party runs from it.
+const FLEE_RANGE: i32 = 4;
+
+/// What an enemy party on the battle map goes after.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapBehavior {
+    /// Head straight for the player's castle.
+    RushBase,
+    /// Chase the nearest player party, or the castle if there are none.
+    Hunt,
+    /// Stay near where the party spawned and attack player parties that come
+    /// within `radius` tiles of it.
+    Guard { radius: i32 },
+}
+
+impl Default for MapBehavior {
+    fn default() -> Self {
+        MapBehavior::RushBase
+    }
+}
+
+/// Map AI settings for a unit, read from its prefab. A party follows the AI
+/// of its first unit.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct MapAi {
+    pub behavior: MapBehavior,
+    /// The party runs from nearby player parties once its total hp drops
+    /// below this fraction. Zero never flees.
+    pub flee_below: f32,
+    /// How likely the party is to stop and wait rather than move when it thinks.
+    pub wait_weight: i32,
+    pub move_weight: i32,
+}
+
+impl Default for MapAi {
+    fn default() -> Self {
+        Self {
+            behavior: MapBehavior::default(),
+            flee_below: 0.0,
+            wait_weight: 1,
+            move_weight: 5,
+        }
+    }
+}
+
+impl MapAi {
+    /// Read the `map_ai`, `guard_radius`, `flee_below`, `wait_weight` and
+    /// `move_weight` fields from a unit prefab's root entity.
+    pub fn from_fields(fields: &Fields) -> Self {
+        let default = Self::default();
+        let behavior = match fields.try_get_str("map_ai").map(str::to_lowercase).as_deref() {
+            Some("hunt") => MapBehavior::Hunt,
+            Some("guard") => MapBehavior::Guard {
+                radius: fields.try_get_i32("guard_radius").unwrap_or(3),
+            },
+            _ => MapBehavior::RushBase,
+        };
+        Self {
+            behavior,
+            flee_below: fields.try_get_f32("flee_below").unwrap_or(default.flee_below),
+            wait_weight: fields.try_get_i32("wait_weight").unwrap_or(default.wait_weight),
+            move_weight: fields.try_get_i32("move_weight").unwrap_or(default.move_weight),
+        }
+    }
+}
+
+/// Where an enemy party started out, for guards to return to.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GuardPost(pub IVec2);
+
+/// What a party decided to do this time it thought.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapGoal {
+    /// Path to this tile.
+    MoveTo(IVec2),
+    /// Step away from a party at this tile.
+    FleeFrom(IVec2),
+    /// Nothing worth doing, stay put.
+    Hold,
+}
+
+fn distance(a: IVec2, b: IVec2) -> i32 {
+    let d = (a - b).abs();
+    d.x.max(d.y)
+}
+
+pub fn get_nearest_player_position(a: IVec2, positions: &[IVec2]) -> Option<IVec2> {
+    let res = positions.iter().map(|b| (a - *b).as_vec2().length());
+    let res = res
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Less))
+        .map(|(index, _)| index);
+    if let Some(i) = res {
+        return Some(positions[i]);
+    }
+    None
+}
+
+/// Decide where a party at `xy` should head. `health` is the fraction of
+/// the party's hp left.
+pub fn choose_goal(
+    ai: &MapAi,
+    xy: IVec2,
+    health: f32,
+    home: Option<IVec2>,
+    players: &[IVec2],
+    base: Option<IVec2>,
+) -> MapGoal {
+    let nearest_player = get_nearest_player_position(xy, players);
+
+    if health < ai.flee_below {
+        if let Some(player) = nearest_player.filter(|p| distance(xy, *p) <= FLEE_RANGE) {
+            return MapGoal::FleeFrom(player);
+        }
+    }
+
+    let target = match ai.behavior {
+        MapBehavior::RushBase => base,
+        MapBehavior::Hunt => nearest_player.or(base),
+        MapBehavior::Guard { radius } => {
+            let home = home.unwrap_or(xy);
+            let intruder = players
+                .iter()
+                .copied()
+                .filter(|p| distance(home, *p) <= radius)
+                .min_by_key(|p| distance(xy, *p));
+            match intruder {
+                Some(intruder) => Some(intruder),
+                None if xy != home => Some(home),
+                None => None,
+            }
+        }
+    };
+    target.map_or(MapGoal::Hold, MapGoal::MoveTo)
+}
+
+#[test]
+fn behaviors_pick_targets() {
+    let base = Some(IVec2::new(10, 0));
+    let players = [IVec2::new(0, 5), IVec2::new(3, 0)];
+    let rush = MapAi::default();
+    let hunt = MapAi {
+        behavior: MapBehavior::Hunt,
+        ..Default::default()
+    };
+    let goal = |ai: &MapAi, players: &[IVec2]| {
+        choose_goal(ai, IVec2::ZERO, 1.0, None, players, base)
+    };
+
+    assert_eq!(goal(&rush, &players), MapGoal::MoveTo(IVec2::new(10, 0)));
+    assert_eq!(goal(&hunt, &players), MapGoal::MoveTo(IVec2::new(3, 0)));
+    assert_eq!(goal(&hunt, &[]), MapGoal::MoveTo(IVec2::new(10, 0)));
+}
+
+#[test]
+fn guards_and_cowards() {
+    let guard = MapAi {
+        behavior: MapBehavior::Guard { radius: 2 },
+        flee_below: 0.5,
+        ..Default::default()
+    };
+    let home = IVec2::new(5, 5);
+    let away = IVec2::new(4, 5);
+    let far = [IVec2::new(9, 9)];
+    let near = [IVec2::new(6, 6)];
+    let goal = |xy: IVec2, health: f32, players: &[IVec2]| {
+        choose_goal(&guard, xy, health, Some(home), players, None)
+    };
+
+    assert_eq!(goal(home, 1.0, &far), MapGoal::Hold);
+    assert_eq!(goal(away, 1.0, &far), MapGoal::MoveTo(home));
+    assert_eq!(goal(away, 1.0, &near), MapGoal::MoveTo(IVec2::new(6, 6)));
+    assert_eq!(goal(away, 0.25, &near), MapGoal::FleeFrom(IVec2::new(6, 6)));
+}
@@ -0,0 +1,243 @@
+use bevy::{app::AppExit, math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ldtk_loader::Fields,
+    party::PartyUnit,
+    unit::{Enemy, Player},
+    GameState, TILE_SIZE,
+};
+
+use super::{waves::WaveSpawner, EnemyBase, EnemyUnit, MapUnit, PlayerBase, PlayerUnit};
+
+/// Health of the player's castle if the map doesn't set an `hp` field.
+pub const PLAYER_BASE_HP: i32 = 20;
+/// Health of enemy spawners and bases if the map doesn't set an `hp` field.
+pub const ENEMY_BASE_HP: i32 = 8;
+
+const DESTROYED_TINT: Color = Color::rgb(0.3, 0.3, 0.3);
+
+pub struct BasesPlugin;
+
+impl Plugin for BasesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::BattleMap)
+                .with_system(siege.label("siege"))
+                .with_system(destroy_bases.label("destroy_bases").after("siege"))
+                .with_system(check_game_over.after("destroy_bases"))
+                .with_system(base_ui),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Victory).with_system(game_over_ui))
+        .add_system_set(SystemSet::on_update(GameState::Defeat).with_system(game_over_ui));
+    }
+}
+
+/// Hit points of a base or spawner on the battle map. Player bases are marked
+/// with [Player], enemy ones with [Enemy].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaseHealth {
+    pub hp: i32,
+    pub max: i32,
+}
+
+impl BaseHealth {
+    pub fn new(max: i32) -> Self {
+        Self { hp: max, max }
+    }
+
+    /// Read the `hp` field from a map entity, falling back to `default`.
+    pub fn from_fields(fields: &Fields, default: i32) -> Self {
+        Self::new(fields.try_get_i32("hp").unwrap_or(default))
+    }
+
+    pub fn take_damage(&mut self, amount: i32) {
+        self.hp = (self.hp - amount).max(0);
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.hp <= 0
+    }
+}
+
+/// Marks a base that has been destroyed and no longer counts.
+#[derive(Component)]
+pub struct Destroyed;
+
+/// Whether a party at `party` is close enough to attack a base at `base`,
+/// both in grid coordinates.
+pub fn in_reach(party: IVec2, base: IVec2) -> bool {
+    let d = (party - base).abs();
+    d.x <= 1 && d.y <= 1
+}
+
+/// Which game over state to enter, if any. The player loses when their base is
+/// destroyed and wins once an enemy base or every enemy spawner is gone.
+pub fn outcome<'a>(
+    player_bases: impl Iterator<Item = &'a BaseHealth>,
+    mut enemy_bases: impl Iterator<Item = &'a BaseHealth>,
+    enemy_spawners: impl Iterator<Item = &'a BaseHealth>,
+) -> Option<GameState> {
+    let mut player_bases = player_bases.peekable();
+    if player_bases.peek().is_some() && player_bases.any(|b| b.is_destroyed()) {
+        return Some(GameState::Defeat);
+    }
+    if enemy_bases.any(|b| b.is_destroyed()) {
+        return Some(GameState::Victory);
+    }
+    let mut enemy_spawners = enemy_spawners.peekable();
+    if enemy_spawners.peek().is_some() && enemy_spawners.all(|b| b.is_destroyed()) {
+        return Some(GameState::Victory);
+    }
+    None
+}
+
+/// Parties that reach an opposing base throw themselves at it, dealing one
+/// damage per unit in the party. The party is spent in the attack.
+fn siege(
+    mut commands: Commands,
+    q_parties: Query<
+        (Entity, &Transform, &Children, Option<&EnemyUnit>, Option<&PlayerUnit>),
+        With<MapUnit>,
+    >,
+    mut q_bases: Query<
+        (&Transform, &mut BaseHealth, Option<&Enemy>, Option<&Player>),
+        Without<Destroyed>,
+    >,
+    q_units: Query<&PartyUnit>,
+) {
+    for (party, transform, children, enemy, player) in q_parties.iter() {
+        let xy = transform.translation.xy().as_ivec2() / TILE_SIZE;
+        for (base_transform, mut health, base_enemy, base_player) in q_bases.iter_mut() {
+            let opposed = (enemy.is_some() && base_player.is_some())
+                || (player.is_some() && base_enemy.is_some());
+            if !opposed || health.is_destroyed() {
+                continue;
+            }
+            let base = base_transform.translation.xy().as_ivec2() / TILE_SIZE;
+            if in_reach(xy, base) {
+                let damage = children.iter().filter(|c| q_units.get(**c).is_ok()).count();
+                health.take_damage(damage as i32);
+                commands.entity(party).despawn_recursive();
+                break;
+            }
+        }
+    }
+}
+
+fn destroy_bases(
+    mut commands: Commands,
+    mut q_bases: Query<(Entity, &BaseHealth, Option<&mut TextureAtlasSprite>), Without<Destroyed>>,
+) {
+    for (entity, health, sprite) in q_bases.iter_mut() {
+        if health.is_destroyed() {
+            if let Some(mut sprite) = sprite {
+                sprite.color = DESTROYED_TINT;
+            }
+            commands
+                .entity(entity)
+                .remove::<super::spawn::Spawner>()
+                .remove::<WaveSpawner>()
+                .insert(Destroyed);
+        }
+    }
+}
+
+fn check_game_over(
+    mut state: ResMut<State<GameState>>,
+    q_player: Query<&BaseHealth, With<Player>>,
+    q_enemy_bases: Query<&BaseHealth, With<EnemyBase>>,
+    q_spawners: Query<&BaseHealth, (With<Enemy>, Without<EnemyBase>)>,
+) {
+    if let Some(next) = outcome(q_player.iter(), q_enemy_bases.iter(), q_spawners.iter()) {
+        // The game ending wins over any other change queued this frame, like
+        // a fight breaking out
+        let _ = state.overwrite_set(next);
+    }
+}
+
+fn base_ui(
+    mut egui: ResMut<EguiContext>,
+    q_player: Query<&BaseHealth, With<PlayerBase>>,
+    q_spawners: Query<&BaseHealth, (With<Enemy>, Without<EnemyBase>)>,
+    q_waves: Query<&WaveSpawner>,
+) {
+    let next_wave = q_waves
+        .iter()
+        .filter_map(WaveSpawner::countdown)
+        .fold(None, |min: Option<f32>, t| Some(min.map_or(t, |m| m.min(t))));
+    let remaining = q_spawners.iter().filter(|b| !b.is_destroyed()).count();
+    egui::Area::new("base_health")
+        .fixed_pos([10.0, 10.0])
+        .show(egui.ctx_mut(), |ui| {
+            for base in q_player.iter() {
+                ui.label(format!("Castle: {}/{}", base.hp, base.max));
+            }
+            ui.label(format!("Enemy spawners left: {}", remaining));
+            if let Some(t) = next_wave {
+                ui.label(format!("Next wave in {:.0}s", t.ceil()));
+            }
+            ui.label("F5 to save");
+        });
+}
+
+fn game_over_ui(
+    mut egui: ResMut<EguiContext>,
+    state: Res<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let heading = match state.current() {
+        GameState::Victory => "Victory! The enemy has been driven off.",
+        _ => "Defeat. Your castle has fallen.",
+    };
+    egui::Window::new("Game Over")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui.ctx_mut(), |ui| {
+            ui.heading(heading);
+            if ui.button("Quit").clicked() {
+                exit.send(AppExit);
+            }
+        });
+}
+
+#[test]
+fn base_damage() {
+    let mut base = BaseHealth::new(5);
+    base.take_damage(3);
+    assert_eq!(base.hp, 2);
+    assert!(!base.is_destroyed());
+    base.take_damage(4);
+    assert_eq!(base.hp, 0);
+    assert!(base.is_destroyed());
+
+    assert!(in_reach(IVec2::new(3, 4), IVec2::new(4, 5)));
+    assert!(in_reach(IVec2::new(4, 5), IVec2::new(4, 5)));
+    assert!(!in_reach(IVec2::new(2, 5), IVec2::new(4, 5)));
+}
+
+#[test]
+fn game_outcome() {
+    let alive = BaseHealth::new(5);
+    let mut dead = BaseHealth::new(5);
+    dead.take_damage(5);
+
+    assert_eq!(outcome([alive].iter(), [alive].iter(), [alive, dead].iter()), None);
+    assert_eq!(outcome([alive].iter(), [dead].iter(), [alive].iter()), Some(GameState::Victory));
+    assert_eq!(outcome([dead].iter(), [dead].iter(), [].iter()), Some(GameState::Defeat));
+    // Nothing to win or lose before the bases are set up.
+    assert_eq!(outcome([].iter(), [].iter(), [].iter()), None);
+}
+
+#[test]
+fn spawners_only_victory() {
+    let alive = BaseHealth::new(5);
+    let mut dead = BaseHealth::new(5);
+    dead.take_damage(5);
+
+    assert_eq!(outcome([alive].iter(), [alive].iter(), [dead, dead].iter()), Some(GameState::Victory));
+    assert_eq!(outcome([alive].iter(), [].iter(), [dead, dead].iter()), Some(GameState::Victory));
+    assert_eq!(outcome([alive].iter(), [].iter(), [dead, alive].iter()), None);
+}
@@ -1,4 +1,4 @@
-use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy::prelude::*;
 use bevy_egui::{egui::{self, FontDefinitions}, EguiContext};
 
 use crate::{
@@ -9,12 +9,12 @@ use crate::{
 };
 
 use super::{
-    map::CollisionMap,
+    map::{CollisionMap, TerrainMap},
     spawn::{
         DespawnTimer, 
         //SpawnEntity
     },
-    MapUnits, EnemyUnit,
+    MapUnits, EnemyUnit, PlayerUnit, TerrainTile,
 };
 
 pub struct MapCombatPlugin;
@@ -34,8 +34,10 @@ impl Plugin for MapCombatPlugin {
 
 fn on_collision(
     mut commands: Commands,
-    q_enemies: Query<(Entity, &Transform), (With<EnemyUnit>, Changed<Transform>)>,
+    q_enemies: Query<&Transform, With<EnemyUnit>>,
+    q_players: Query<(), With<PlayerUnit>>,
     units: Res<MapUnits>,
+    terrain: Res<TerrainMap>,
     mut state: ResMut<State<GameState>>,
     config: Res<Assets<ConfigAsset>>,
     ldtk: Res<Assets<LdtkMap>>,
@@ -43,9 +45,13 @@ fn on_collision(
 ) {
     if let Some(config) = config.get(SETTINGS_PATH) {
         if let Some(ldtk) = ldtk.get(&config.settings.map_file) {
-            for (enemy, transform) in q_enemies.iter() {
-                let xy = units.xy_to_grid(transform.translation.xy());
-                if let Some(player) = units.get_from_grid_xy(xy) {
+            // A fight breaks out wherever a player and enemy party meet
+            for (xy, player, enemy) in units.contested() {
+                if let (Ok(transform), Ok(_)) = (q_enemies.get(enemy), q_players.get(player)) {
+                    // Something else, like the game ending, already changed state
+                    if state.set(GameState::BeginningCombat).is_err() {
+                        return;
+                    }
                     let mut pos = transform.translation;
                     pos += Vec3::new(0.0, 0.0, 1.0) * TILE_SIZE as f32;
                     let mut text_pos = Vec3::new(0.0, 1.0, 0.0) * TILE_SIZE as f32;
@@ -73,11 +79,14 @@ fn on_collision(
                     let text = commands.spawn_bundle(text).id();
                     commands.spawn_bundle(sprite)
                     .insert(DespawnTimer::new(3.0))
-                    .insert(BeginCombat { player_party: player, enemy_party: enemy })
+                    .insert(BeginCombat {
+                        player_party: player,
+                        enemy_party: enemy,
+                        terrain: terrain.get(xy),
+                    })
                     .add_child(text)
                     ;
 
-                    state.set(GameState::BeginningCombat).unwrap();
                     return;
                 }
             }
@@ -90,6 +99,7 @@ fn on_collision(
 pub struct BeginCombat {
     player_party: Entity,
     enemy_party: Entity,
+    terrain: TerrainTile,
 }
 
 fn begin_combat(
@@ -117,6 +127,7 @@ fn begin_combat(
             commands.spawn().insert(ArenaCombat {
                 player_party: begin.player_party,
                 enemy_party: begin.enemy_party,
+                terrain: begin.terrain,
             });
         }
     }
@@ -1,14 +1,14 @@
 use bevy::{prelude::*, ecs::system::EntityCommands, math::Vec3Swizzles};
-use rand::{thread_rng, prelude::IteratorRandom, Rng};
+use rand::{prelude::IteratorRandom, Rng};
 
 use crate::{ldtk_loader::{LdtkMap, Tags, Fields}, GameState, 
 AtlasHandles, animation::Animator, SETTINGS_PATH, config::ConfigAsset, 
 //SpawnPrefabOld, 
 prefab::Prefabs, TILE_SIZE, battle_map::{UnitCommands}, party::{GenerateParty, Party, PartyUnit}, 
 
-GENERATE_PARTY_SYSTEM, LdtkHandles, unit::Enemy};
+GENERATE_PARTY_SYSTEM, LdtkHandles, rng::{GameRng, PARTY_STREAM, SPAWN_STREAM}, party_template::PartyTemplate, unit::Enemy};
 
-use super::{map::{BUILD_MAP_SYSTEM, CollisionMap}, spawn::Spawner, MapUnit, BattleMapEntity, MapUnits, get_valid_spawn_points, PlayerBase, EnemyUnit, UnitCommand};
+use super::{ai::GuardPost, map::{BUILD_MAP_SYSTEM, CollisionMap}, spawn::{Spawner, SpawnTemplate}, MapUnit, BattleMapEntity, MapUnits, get_valid_spawn_points, EnemyBase, EnemyUnit, UnitCommand, bases::{BaseHealth, ENEMY_BASE_HP}, waves::WaveSpawner, population::{EnemyParties, under_cap}};
 
 //use super::Spawner;
 
@@ -37,25 +37,38 @@ fn setup(
     q_tags: Query<(Entity, &Tags, &Fields, &Name), (Added<Tags>, With<BattleMapEntity>)>,
     mut ldtk_handles: ResMut<LdtkHandles>,
     config: Res<Assets<ConfigAsset>>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     if let Some(config) = config.get(SETTINGS_PATH) {
 
         for (entity, tags, fields, name) in q_tags.iter() {
             if tags.has_all(&["enemy", "spawner"]) {
-                if let Some(min) = fields.try_get_f32("spawn_delay_min") {
+                commands
+                    .entity(entity)
+                    .insert(Enemy)
+                    .insert(BaseHealth::from_fields(fields, ENEMY_BASE_HP));
+                if let Some(schedule) = fields.try_get_str("waves") {
+                    commands.entity(entity).insert(WaveSpawner::new(schedule));
+                } else if let Some(min) = fields.try_get_f32("spawn_delay_min") {
                     if let Some(max) = fields.try_get_f32("spawn_delay_max") {
-                        let mut rng = thread_rng();
-                        let delay: f32 = rng.gen_range(min..max);
+                        let delay: f32 = game_rng.stream(SPAWN_STREAM).gen_range(min..max);
                         //println!("Spawning enemy spawner!");
                         commands.entity(entity).insert(
                             Spawner(Timer::from_seconds(delay, true))
                         ).insert(Enemy)
                         ;
+                        if let Some(template) = fields.try_get_str("party") {
+                            commands.entity(entity).insert(SpawnTemplate(template.to_owned()));
+                        }
                     }
                 }
             }
             if tags.has("enemy_base") {
-                commands.entity(entity).insert(PlayerBase);
+                commands
+                    .entity(entity)
+                    .insert(EnemyBase)
+                    .insert(Enemy)
+                    .insert(BaseHealth::from_fields(fields, ENEMY_BASE_HP));
             }
         }
     
@@ -70,15 +83,19 @@ fn setup(
 fn spawn(
     mut commands: Commands,
     time: Res<Time>,
-    mut q_spawner: Query<(&Transform, &mut Spawner), With<Enemy>>,
+    mut q_spawner: Query<(&Transform, &mut Spawner, Option<&SpawnTemplate>), With<Enemy>>,
+    q_parties: EnemyParties,
     map_units: Res<MapUnits>,
     colliders: Res<CollisionMap>,
     ldtk: Res<Assets<LdtkMap>>,
     config: Res<Assets<ConfigAsset>>,
+    prefabs: Res<Prefabs>,
+    templates: Res<Assets<PartyTemplate>>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     if let Some(config) = config.get(SETTINGS_PATH) {
 
-        for (transform, mut spawner) in q_spawner.iter_mut() {
+        for (transform, mut spawner, template) in q_spawner.iter_mut() {
 
             // Ensure all our prefabs are loaded
             for name in config.settings.enemy_units.iter() {
@@ -89,23 +106,29 @@ fn spawn(
     
             spawner.tick(time.delta());
     
-            if spawner.just_finished() {
+            if spawner.just_finished() && under_cap(q_parties.iter().count(), config.settings.enemy_party_cap) {
     
                 let p = transform.translation;
                 let curr = map_units.xy_to_grid(p.xy());
                 let spawn_points = get_valid_spawn_points(curr, &map_units, &colliders);
     
                 if let Some(spawn_points) = spawn_points {
-                    let mut rng = thread_rng(); 
-                    let spawn = spawn_points.choose(&mut rng).unwrap();
+                    let spawn = spawn_points.choose(game_rng.stream(SPAWN_STREAM)).unwrap();
                     let spawn = map_units.grid_to_xy(spawn);
                     
                     let pos = Vec3::new(spawn.x, spawn.y, 2.0);
     
-                    let names = config.settings.enemy_units.iter().map(|s|s.to_string()).collect();
+                    let template = template.and_then(|t| {
+                        let found = prefabs.party_template(&t.0, &templates);
+                        if found.is_none() {
+                            warn!("Unknown party template {}", t.0);
+                        }
+                        found.cloned()
+                    }).unwrap_or_else(|| PartyTemplate::random(&config.settings.enemy_units, 4));
+                    let units = template.roll(game_rng.stream(PARTY_STREAM));
                     //println!("Spawning gen...");
                     commands.spawn().insert(
-                        GenerateParty::new(4, names, pos),
+                        GenerateParty::from_units(units, pos),
                     ).insert(Enemy);
     
                 } else {
@@ -127,8 +150,10 @@ fn on_spawn(
             let mut unit_commands = UnitCommands::new(configs.settings.map_move_speed, configs.settings.map_move_wait);
             unit_commands.queue.push_back(UnitCommand::AiThink());
             //println!("Atlas {:?}", atlas.get(unit_atlas).unwrap());
+            let home = party_transform.translation.xy().as_ivec2() / TILE_SIZE;
             commands.entity(party)
             .insert(unit_commands)
+            .insert(GuardPost(home))
             .insert(EnemyUnit)
             .insert(MapUnit);
         }
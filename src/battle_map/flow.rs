@@ -0,0 +1,206 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+
+use crate::{GameState, TILE_SIZE};
+
+use super::{
+    map::{CollisionMap, TerrainMap},
+    pathing::DIRECTIONS,
+    MapUnit, PlayerBase, PlayerUnit,
+};
+
+pub const FLOW_FIELD_SYSTEM: &str = "update_flow_fields";
+
+pub struct FlowPlugin;
+
+impl Plugin for FlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowFields>().add_system_set(
+            SystemSet::on_update(GameState::BattleMap)
+                .with_system(update_flow_fields.label(FLOW_FIELD_SYSTEM)),
+        );
+    }
+}
+
+/// The cost of the cheapest path from every tile on the map to a goal, so a
+/// party anywhere can find its next step without searching.
+pub struct FlowField {
+    size: IVec2,
+    costs: Vec<u32>,
+}
+
+impl FlowField {
+    /// Build a field out from `goal`. `cost` gives the cost of stepping onto a
+    /// tile, or `None` if it can't be entered. The goal itself is always
+    /// reachable, so an obstacle like a castle can be headed for.
+    pub fn new(goal: IVec2, size: IVec2, cost: impl Fn(IVec2) -> Option<u32>) -> Self {
+        let mut field = Self {
+            size,
+            costs: vec![u32::MAX; (size.x * size.y).max(0) as usize],
+        };
+        let i = match field.index(goal) {
+            Some(i) => i,
+            None => return field,
+        };
+        field.costs[i] = 0;
+
+        let mut open = BinaryHeap::new();
+        open.push(Reverse((0, goal.to_array())));
+        while let Some(Reverse((dist, curr))) = open.pop() {
+            let curr = IVec2::from(curr);
+            if dist > field.costs[field.index(curr).unwrap()] {
+                continue;
+            }
+            // Walking from a neighbor onto this tile costs this tile's cost.
+            // The goal may be an obstacle, which has no cost of its own.
+            let step = cost(curr).unwrap_or(1);
+            for dir in DIRECTIONS {
+                let next = curr + dir;
+                let i = match field.index(next) {
+                    Some(i) => i,
+                    None => continue,
+                };
+                if cost(next).is_none() {
+                    continue;
+                }
+                let next_dist = dist + step;
+                if next_dist < field.costs[i] {
+                    field.costs[i] = next_dist;
+                    open.push(Reverse((next_dist, next.to_array())));
+                }
+            }
+        }
+        field
+    }
+
+    fn index(&self, xy: IVec2) -> Option<usize> {
+        if xy.cmplt(IVec2::ZERO).any() || xy.cmpge(self.size).any() {
+            return None;
+        }
+        Some((xy.y * self.size.x + xy.x) as usize)
+    }
+
+    /// Cost of the cheapest path from `xy` to the goal, if there is one.
+    pub fn cost(&self, xy: IVec2) -> Option<u32> {
+        self.index(xy)
+            .map(|i| self.costs[i])
+            .filter(|c| *c != u32::MAX)
+    }
+
+    /// The neighbor of `xy` closest to the goal that `can_enter` allows. Only
+    /// steps that get closer to the goal are taken, so a party held up by
+    /// another waits rather than wandering off.
+    pub fn next_step(&self, xy: IVec2, can_enter: impl Fn(IVec2) -> bool) -> Option<IVec2> {
+        let here = self.cost(xy).unwrap_or(u32::MAX);
+        DIRECTIONS
+            .iter()
+            .map(|dir| xy + *dir)
+            .filter_map(|p| self.cost(p).map(|c| (c, p)))
+            .filter(|(c, p)| *c < here && can_enter(*p))
+            .min_by_key(|(c, _)| *c)
+            .map(|(_, p)| p)
+    }
+}
+
+/// Flow fields toward the player's castle and every player party, by goal tile.
+#[derive(Default)]
+pub struct FlowFields {
+    fields: HashMap<IVec2, FlowField>,
+    /// The goals the fields were last built for.
+    goals: Vec<IVec2>,
+}
+
+impl FlowFields {
+    pub fn get(&self, goal: IVec2) -> Option<&FlowField> {
+        self.fields.get(&goal)
+    }
+}
+
+fn grid_xy(transform: &Transform) -> IVec2 {
+    transform.translation.xy().as_ivec2() / TILE_SIZE
+}
+
+/// Build fields for new goals, or rebuild them all when the map changes. Parties in the way
+/// move on soon enough, so they're left for each party to check as it steps.
+fn update_flow_fields(
+    mut flow: ResMut<FlowFields>,
+    map: Res<CollisionMap>,
+    terrain: Res<TerrainMap>,
+    q_parties: Query<&Transform, (With<PlayerUnit>, With<MapUnit>)>,
+    q_bases: Query<&Transform, With<PlayerBase>>,
+) {
+    let mut goals: Vec<IVec2> = q_parties.iter().chain(q_bases.iter()).map(grid_xy).collect();
+    goals.sort_by_key(|g| g.to_array());
+    goals.dedup();
+
+    let map_changed = map.is_changed() || terrain.is_changed();
+    if flow.goals == goals && !map_changed {
+        return;
+    }
+
+    // Fields for goals that haven't moved are still good unless the map changed
+    let mut old = match map_changed {
+        true => HashMap::default(),
+        false => std::mem::take(&mut flow.fields),
+    };
+    let size = map.size().as_ivec2();
+    let cost = |p: IVec2| match map.is_obstacle(p.to_array()) {
+        true => None,
+        false => Some(terrain.move_cost(p)),
+    };
+    flow.fields = goals
+        .iter()
+        .map(|goal| {
+            let field = old
+                .remove(goal)
+                .unwrap_or_else(|| FlowField::new(*goal, size, &cost));
+            (*goal, field)
+        })
+        .collect();
+    flow.goals = goals;
+}
+
+#[test]
+fn field_leads_to_goal() {
+    // A wall down the middle with a gap at the top
+    let size = IVec2::new(5, 5);
+    let cost = |p: IVec2| match p.x == 2 && p.y < 4 {
+        true => None,
+        false => Some(1),
+    };
+    let goal = IVec2::new(4, 0);
+    let field = FlowField::new(goal, size, cost);
+
+    assert_eq!(field.cost(goal), Some(0));
+    assert_eq!(field.cost(IVec2::new(2, 0)), None);
+    assert_eq!(field.cost(IVec2::new(0, 0)), Some(12));
+
+    let mut xy = IVec2::new(0, 0);
+    let mut steps = 0;
+    while xy != goal {
+        xy = field.next_step(xy, |_| true).unwrap();
+        assert!(cost(xy).is_some());
+        steps += 1;
+    }
+    assert_eq!(steps, 12);
+}
+
+#[test]
+fn obstacle_goals_are_reachable() {
+    let size = IVec2::new(3, 1);
+    let goal = IVec2::new(2, 0);
+    let field = FlowField::new(goal, size, |p| if p == goal { None } else { Some(2) });
+    assert_eq!(field.next_step(IVec2::new(1, 0), |_| true), Some(goal));
+    assert_eq!(field.cost(IVec2::ZERO), Some(3));
+}
+
+#[test]
+fn steps_around_occupied_tiles() {
+    let size = IVec2::new(3, 3);
+    let field = FlowField::new(IVec2::new(2, 2), size, |_| Some(1));
+    let occupied = IVec2::new(1, 0);
+    let step = field.next_step(IVec2::ZERO, |p| p != occupied);
+    assert_eq!(step, Some(IVec2::new(0, 1)));
+    assert_eq!(field.next_step(IVec2::new(1, 2), |p| p != IVec2::new(2, 2)), None);
+}
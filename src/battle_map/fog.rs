@@ -0,0 +1,319 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use sark_pathfinding::PathMap2d;
+
+use crate::{
+    make_sprite,
+    party::{PartyUnit, ShowMapSprite, SHOW_MAP_SPRITE_SYSTEM},
+    unit::Stats,
+    GameState, TILE_SIZE,
+};
+
+use super::{map::CollisionMap, BattleMapEntity, EnemyUnit, MapUnit, PlayerBase, PlayerUnit};
+
+/// How far the player's castle can see.
+pub const BASE_SIGHT: i32 = 3;
+
+const FOG_DEPTH: i32 = 8;
+const UNEXPLORED_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+const EXPLORED_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.55);
+const VISIBLE_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FogMap>().add_system_set(
+            SystemSet::on_update(GameState::BattleMap)
+                .with_system(update_fog.label("update_fog"))
+                .with_system(draw_fog.after("update_fog"))
+                .with_system(
+                    hide_enemies
+                        .after("update_fog")
+                        .after(SHOW_MAP_SPRITE_SYSTEM),
+                ),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FogTile {
+    Unexplored,
+    Explored,
+    Visible,
+}
+
+impl Default for FogTile {
+    fn default() -> Self {
+        FogTile::Unexplored
+    }
+}
+
+/// What the player knows about each tile of the battle map.
+#[derive(Default)]
+pub struct FogMap {
+    size: IVec2,
+    tiles: Vec<FogTile>,
+}
+
+impl FogMap {
+    pub fn new(size: IVec2) -> Self {
+        Self {
+            size,
+            tiles: vec![FogTile::Unexplored; (size.x * size.y) as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec2 {
+        self.size
+    }
+
+    fn index(&self, xy: IVec2) -> Option<usize> {
+        if xy.cmplt(IVec2::ZERO).any() || xy.cmpge(self.size).any() {
+            return None;
+        }
+        Some((xy.y * self.size.x + xy.x) as usize)
+    }
+
+    pub fn get(&self, xy: IVec2) -> FogTile {
+        self.index(xy).map_or(FogTile::Unexplored, |i| self.tiles[i])
+    }
+
+    pub fn is_visible(&self, xy: IVec2) -> bool {
+        self.get(xy) == FogTile::Visible
+    }
+
+    pub fn is_explored(&self, xy: IVec2) -> bool {
+        self.get(xy) != FogTile::Unexplored
+    }
+
+    /// Visible tiles become explored until something sees them again.
+    pub fn fade(&mut self) {
+        for tile in self.tiles.iter_mut().filter(|t| **t == FogTile::Visible) {
+            *tile = FogTile::Explored;
+        }
+    }
+
+    /// Mark tiles within `radius` of `origin` visible, unless a blocked tile
+    /// stands in the way. Blocked tiles themselves can still be seen.
+    pub fn reveal(&mut self, origin: IVec2, radius: i32, blocked: impl Fn(IVec2) -> bool) {
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                if x * x + y * y > radius * radius {
+                    continue;
+                }
+                let xy = origin + IVec2::new(x, y);
+                if let Some(i) = self.index(xy) {
+                    if line_of_sight(origin, xy, &blocked) {
+                        self.tiles[i] = FogTile::Visible;
+                    }
+                }
+            }
+        }
+    }
+
+    /// A copy of `map` with the obstacles the player hasn't seen left out, so
+    /// paths through unexplored tiles are planned as if they were open.
+    pub fn known_obstacles(&self, map: &CollisionMap) -> PathMap2d {
+        let size = map.size();
+        let mut known = PathMap2d::new(size.to_array());
+        for y in 0..size.y as i32 {
+            for x in 0..size.x as i32 {
+                let xy = IVec2::new(x, y);
+                if self.is_explored(xy) && map.is_obstacle(xy.to_array()) {
+                    let i = known.to_index(xy.into());
+                    known.toggle_obstacle_index(i);
+                }
+            }
+        }
+        known
+    }
+}
+
+/// Tiles on a line from `a` to `b`, both ends included.
+pub fn line(a: IVec2, b: IVec2) -> Vec<IVec2> {
+    let d = (b - a).abs();
+    let step = (b - a).signum();
+    let mut err = d.x - d.y;
+    let mut p = a;
+    let mut points = vec![p];
+    while p != b {
+        let e2 = err * 2;
+        if e2 > -d.y {
+            err -= d.y;
+            p.x += step.x;
+        }
+        if e2 < d.x {
+            err += d.x;
+            p.y += step.y;
+        }
+        points.push(p);
+    }
+    points
+}
+
+fn line_of_sight(a: IVec2, b: IVec2, blocked: impl Fn(IVec2) -> bool) -> bool {
+    let points = line(a, b);
+    points.len() < 3 || !points[1..points.len() - 1].iter().any(|p| blocked(*p))
+}
+
+fn grid_xy(transform: &Transform) -> IVec2 {
+    transform.translation.xy().as_ivec2() / TILE_SIZE
+}
+
+fn update_fog(
+    mut fog: ResMut<FogMap>,
+    map: Res<CollisionMap>,
+    q_parties: Query<(&Transform, &Children), (With<PlayerUnit>, With<MapUnit>)>,
+    q_bases: Query<&Transform, With<PlayerBase>>,
+    q_stats: Query<&Stats>,
+) {
+    let size = map.size().as_ivec2();
+    if fog.size() != size {
+        *fog = FogMap::new(size);
+    }
+
+    fog.fade();
+    let blocked = |xy: IVec2| map.is_obstacle_bounds_checked(xy);
+    for (transform, children) in q_parties.iter() {
+        let sight = children
+            .iter()
+            .filter_map(|c| q_stats.get(*c).ok())
+            .map(Stats::sight)
+            .max()
+            .unwrap_or(0);
+        fog.reveal(grid_xy(transform), sight, blocked);
+    }
+    for transform in q_bases.iter() {
+        fog.reveal(grid_xy(transform), BASE_SIGHT, blocked);
+    }
+}
+
+#[derive(Component)]
+struct FogSprite(IVec2);
+
+fn draw_fog(
+    mut commands: Commands,
+    fog: Res<FogMap>,
+    mut q_sprites: Query<(&FogSprite, &mut Sprite)>,
+) {
+    let size = fog.size();
+    if q_sprites.is_empty() {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let xy = IVec2::new(x, y);
+                let pos = (xy * TILE_SIZE).as_vec2() + Vec2::splat(TILE_SIZE as f32 / 2.0);
+                make_sprite(&mut commands, pos, FOG_DEPTH, UNEXPLORED_COLOR)
+                    .insert(FogSprite(xy))
+                    .insert(BattleMapEntity);
+            }
+        }
+        return;
+    }
+
+    for (tile, mut sprite) in q_sprites.iter_mut() {
+        let color = match fog.get(tile.0) {
+            FogTile::Unexplored => UNEXPLORED_COLOR,
+            FogTile::Explored => EXPLORED_COLOR,
+            FogTile::Visible => VISIBLE_COLOR,
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+/// Marks an enemy party the player can't currently see.
+#[derive(Component)]
+pub struct FogHidden;
+
+fn hide_enemies(
+    mut commands: Commands,
+    fog: Res<FogMap>,
+    q_enemies: Query<
+        (Entity, &Transform, &Children, Option<&FogHidden>),
+        (With<EnemyUnit>, With<MapUnit>),
+    >,
+    q_units: Query<&PartyUnit>,
+    mut q_vis: Query<&mut Visibility>,
+) {
+    for (party, transform, children, hidden) in q_enemies.iter() {
+        let units = children.iter().filter(|c| q_units.get(**c).is_ok());
+        match (fog.is_visible(grid_xy(transform)), hidden.is_some()) {
+            // Something may have shown a hidden party's sprite, like a newly
+            // built party picking its map sprite, so keep them all hidden
+            (false, hidden) => {
+                for unit in units {
+                    if let Ok(mut vis) = q_vis.get_mut(q_units.get(*unit).unwrap().map_sprite()) {
+                        if vis.is_visible {
+                            vis.is_visible = false;
+                        }
+                    }
+                }
+                if !hidden {
+                    commands.entity(party).insert(FogHidden);
+                }
+            }
+            (true, true) => {
+                if let Some(unit) = units.cloned().next() {
+                    commands.entity(unit).insert(ShowMapSprite);
+                }
+                commands.entity(party).remove::<FogHidden>();
+            }
+            (true, false) => {}
+        }
+    }
+}
+
+#[test]
+fn lines() {
+    assert_eq!(line(IVec2::ZERO, IVec2::new(3, 0)).len(), 4);
+    assert_eq!(line(IVec2::ZERO, IVec2::new(2, 2)), vec![IVec2::ZERO, IVec2::ONE, IVec2::splat(2)]);
+    assert_eq!(line(IVec2::new(2, 1), IVec2::new(2, 1)), vec![IVec2::new(2, 1)]);
+}
+
+#[test]
+fn walls_block_sight() {
+    let mut fog = FogMap::new(IVec2::new(10, 10));
+    let wall = IVec2::new(5, 5);
+    fog.reveal(IVec2::new(3, 5), 4, |xy| xy == wall);
+
+    assert!(fog.is_visible(IVec2::new(4, 5)));
+    // The wall can be seen but not past it
+    assert!(fog.is_visible(wall));
+    assert!(!fog.is_visible(IVec2::new(6, 5)));
+    assert!(fog.is_visible(IVec2::new(3, 8)));
+    assert!(!fog.is_explored(IVec2::new(9, 9)));
+
+    fog.fade();
+    assert_eq!(fog.get(IVec2::new(4, 5)), FogTile::Explored);
+}
+
+#[cfg(test)]
+use crate::party::show_map_sprite;
+
+#[test]
+fn enemies_spawned_in_fog_stay_hidden() {
+    let mut world = World::default();
+    world.insert_resource(FogMap::new(IVec2::new(8, 8)));
+    let sprite = world.spawn().insert(Visibility { is_visible: false }).id();
+    let unit = world
+        .spawn()
+        .insert(PartyUnit { map_sprite: sprite, arena_sprite: sprite })
+        .insert(ShowMapSprite)
+        .id();
+    let pos = (IVec2::new(3, 3) * TILE_SIZE).as_vec2().extend(0.0);
+    world
+        .spawn()
+        .insert(EnemyUnit)
+        .insert(MapUnit)
+        .insert(Transform::from_translation(pos))
+        .push_children(&[unit]);
+
+    let mut stage = SystemStage::parallel()
+        .with_system(show_map_sprite.label(SHOW_MAP_SPRITE_SYSTEM))
+        .with_system(hide_enemies.after(SHOW_MAP_SPRITE_SYSTEM));
+    for _ in 0..3 {
+        stage.run(&mut world);
+        assert!(!world.get::<Visibility>(sprite).unwrap().is_visible);
+    }
+}
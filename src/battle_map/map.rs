@@ -13,7 +13,7 @@ use crate::{
     SETTINGS_PATH, TILE_SIZE, animation::{Animator, Animation},
 };
 
-use super::{ EnemyUnit, MapUnit, PlayerUnit, MapLoaded, BattleMapEntity,
+use super::{ EnemyUnit, MapUnit, PlayerUnit, MapLoaded, BattleMapEntity, UnitCommands,
 };
 
 pub struct MapPlugin;
@@ -22,6 +22,7 @@ impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MapUnits>()
             .init_resource::<CollisionMap>()
+            .init_resource::<TerrainMap>()
             .init_resource::<BattleMapLdtkHandle>()
             .add_system_set(SystemSet::on_update(GameState::LoadBattleMap)
                 .with_system(build_map)
@@ -54,16 +55,120 @@ impl Default for TerrainTile {
     }
 }
 
+/// Cost of stepping onto grass. Other terrain costs are relative to it, so a
+/// unit crosses grass in exactly its move time.
+pub const BASE_MOVE_COST: u32 = 4;
+
+impl TerrainTile {
+    pub const ALL: [TerrainTile; 5] = [
+        TerrainTile::Dirt,
+        TerrainTile::Grass,
+        TerrainTile::Mountain,
+        TerrainTile::Mud,
+        TerrainTile::Water,
+    ];
+
+    /// The name of the tile enum value in LDtk, lowercased.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerrainTile::Dirt => "dirt",
+            TerrainTile::Grass => "grass",
+            TerrainTile::Mountain => "mountain",
+            TerrainTile::Mud => "mud",
+            TerrainTile::Water => "water",
+        }
+    }
+
+    pub fn move_cost(&self) -> u32 {
+        match self {
+            TerrainTile::Dirt => 3,
+            TerrainTile::Grass => BASE_MOVE_COST,
+            TerrainTile::Mud => 6,
+            TerrainTile::Mountain => 8,
+            TerrainTile::Water => 10,
+        }
+    }
+
+    /// How much longer than usual it takes to move onto this terrain.
+    pub fn move_scale(&self) -> f32 {
+        self.move_cost() as f32 / BASE_MOVE_COST as f32
+    }
+}
+
+/// The terrain of each tile on the battle map.
+#[derive(Default)]
+pub struct TerrainMap {
+    tiles: Vec<TerrainTile>,
+    size: IVec2,
+}
+impl TerrainMap {
+    pub fn resize(&mut self, size: IVec2) {
+        self.tiles = vec![TerrainTile::default(); (size.x * size.y) as usize];
+        self.size = size;
+    }
+
+    #[inline]
+    pub fn get(&self, grid_xy: IVec2) -> TerrainTile {
+        if grid_xy.cmplt(IVec2::ZERO).any() || grid_xy.cmpge(self.size).any() {
+            return TerrainTile::default();
+        }
+        self.tiles[(grid_xy.y * self.size.x + grid_xy.x) as usize]
+    }
+
+    #[inline]
+    pub fn set(&mut self, grid_xy: IVec2, tile: TerrainTile) {
+        if grid_xy.cmplt(IVec2::ZERO).any() || grid_xy.cmpge(self.size).any() {
+            return;
+        }
+        self.tiles[(grid_xy.y * self.size.x + grid_xy.x) as usize] = tile;
+    }
+
+    #[inline]
+    pub fn move_cost(&self, grid_xy: IVec2) -> u32 {
+        self.get(grid_xy).move_cost()
+    }
+
+    pub fn size(&self) -> IVec2 {
+        self.size
+    }
+}
+
+/// Which side a party on the battle map fights for.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+impl Faction {
+    #[inline]
+    fn index(self) -> usize {
+        match self {
+            Faction::Player => 0,
+            Faction::Enemy => 1,
+        }
+    }
+}
+
+#[derive(Default, Copy, Clone, Debug)]
+struct MapTile {
+    /// The party standing on this tile for each faction.
+    units: [Option<Entity>; 2],
+    /// The party on its way onto this tile for each faction.
+    reserved: [Option<Entity>; 2],
+}
+
+/// Where the parties of both factions are on the battle map.
 #[derive(Default)]
 pub struct MapUnits {
-    units: Vec<Option<Entity>>,
+    tiles: Vec<MapTile>,
     size: IVec2,
 }
 impl MapUnits {
     pub fn new(size: IVec2) -> Self {
         let len = size.x * size.y;
         Self {
-            units: vec![None; len as usize],
+            tiles: vec![MapTile::default(); len as usize],
             size,
         }
     }
@@ -90,7 +195,7 @@ impl MapUnits {
 
     pub fn resize(&mut self, size: IVec2) {
         let len = size.x * size.y;
-        self.units = vec![None; len as usize];
+        self.tiles = vec![MapTile::default(); len as usize];
         self.size = size;
     }
 
@@ -106,40 +211,96 @@ impl MapUnits {
 
     #[inline]
     pub fn clear(&mut self) {
-        self.units.iter_mut().for_each(|u| *u = None);
+        self.tiles.iter_mut().for_each(|t| *t = MapTile::default());
     }
 
     #[inline]
-    pub fn get_from_xy(&self, xy: Vec2) -> Option<Entity> {
-        let i = self.xy_to_index(xy);
-        self.units[i]
+    fn tile(&self, grid_xy: IVec2) -> Option<&MapTile> {
+        if grid_xy.cmplt(IVec2::ZERO).any() || grid_xy.cmpge(self.size).any() {
+            return None;
+        }
+        self.tiles.get(self.grid_to_index(grid_xy))
     }
 
     #[inline]
-    pub fn get_from_grid_xy(&self, grid_xy: IVec2) -> Option<Entity> {
+    fn tile_mut(&mut self, grid_xy: IVec2) -> Option<&mut MapTile> {
         if grid_xy.cmplt(IVec2::ZERO).any() || grid_xy.cmpge(self.size).any() {
             return None;
         }
         let i = self.grid_to_index(grid_xy);
-        self.units[i]
+        self.tiles.get_mut(i)
+    }
+
+    /// The party on the tile at `xy`, player parties first.
+    #[inline]
+    pub fn get_from_xy(&self, xy: Vec2) -> Option<Entity> {
+        self.get_from_grid_xy(self.xy_to_grid(xy))
+    }
+
+    /// The party on the tile at `grid_xy`, player parties first.
+    #[inline]
+    pub fn get_from_grid_xy(&self, grid_xy: IVec2) -> Option<Entity> {
+        let tile = self.tile(grid_xy)?;
+        tile.units[0].or(tile.units[1])
     }
     #[inline]
     pub fn get_from_index(&self, index: usize) -> Option<Entity> {
-        self.units[index]
+        let tile = &self.tiles[index];
+        tile.units[0].or(tile.units[1])
     }
 
+    /// The party of `faction` on the tile at `grid_xy`.
     #[inline]
-    pub fn set_from_grid_xy(&mut self, grid_xy: IVec2, entity: Entity) {
-        //println!("xy in {}", grid_xy);
-        let i = self.grid_to_index(grid_xy);
-        self.units[i] = Some(entity)
+    pub fn get_faction(&self, grid_xy: IVec2, faction: Faction) -> Option<Entity> {
+        self.tile(grid_xy)?.units[faction.index()]
     }
 
     #[inline]
-    pub fn set_from_xy(&mut self, xy: Vec2, entity: Entity) {
-        let i = self.xy_to_index(xy);
-        //println!("inserting at {}", i);
-        self.units[i] = Some(entity);
+    pub fn set_from_grid_xy(&mut self, grid_xy: IVec2, entity: Entity, faction: Faction) {
+        if let Some(tile) = self.tile_mut(grid_xy) {
+            tile.units[faction.index()] = Some(entity);
+        }
+    }
+
+    #[inline]
+    pub fn set_from_xy(&mut self, xy: Vec2, entity: Entity, faction: Faction) {
+        self.set_from_grid_xy(self.xy_to_grid(xy), entity, faction);
+    }
+
+    /// Hold the tile at `grid_xy` for a party that's moving onto it.
+    #[inline]
+    pub fn reserve(&mut self, grid_xy: IVec2, entity: Entity, faction: Faction) {
+        if let Some(tile) = self.tile_mut(grid_xy) {
+            tile.reserved[faction.index()] = Some(entity);
+        }
+    }
+
+    /// No party is on the tile or on its way there.
+    pub fn is_free(&self, grid_xy: IVec2) -> bool {
+        self.tile(grid_xy).map_or(false, |tile| {
+            tile.units.iter().chain(tile.reserved.iter()).all(Option::is_none)
+        })
+    }
+
+    /// Whether `entity` can move onto the tile at `grid_xy`. Parties don't
+    /// share a tile with another party of their own faction, but can move onto
+    /// an opposing one to fight it.
+    pub fn can_enter(&self, grid_xy: IVec2, entity: Entity, faction: Faction) -> bool {
+        self.tile(grid_xy).map_or(false, |tile| {
+            let i = faction.index();
+            let other = |e: Option<Entity>| e.map_or(false, |e| e != entity);
+            !other(tile.units[i]) && !other(tile.reserved[i])
+        })
+    }
+
+    /// Tiles where a player and an enemy party meet, with the player and
+    /// enemy party on each.
+    pub fn contested(&self) -> impl Iterator<Item = (IVec2, Entity, Entity)> + '_ {
+        let width = self.size.x.max(1);
+        self.tiles.iter().enumerate().filter_map(move |(i, tile)| {
+            let xy = IVec2::new(i as i32 % width, i as i32 / width);
+            Some((xy, tile.units[0]?, tile.units[1]?))
+        })
     }
 
     pub fn size(&self) -> IVec2 {
@@ -203,6 +364,7 @@ fn build_map(
     mut atlas_handles: ResMut<AtlasHandles>,
     mut atlases: ResMut<Assets<TextureAtlas>>,
     mut map: ResMut<CollisionMap>,
+    mut terrain: ResMut<TerrainMap>,
     //mut q_cam: Query<&mut TiledProjection>,
     mut units: ResMut<MapUnits>,
     q_loaded: Query<&MapLoaded>,
@@ -220,6 +382,7 @@ fn build_map(
 
 
             units.resize(map.size().as_ivec2());
+            terrain.resize(map.size().as_ivec2());
             for (i, layer) in ldtk.layers().enumerate() {
                 match layer {
                     MapLayer::Tiles(layer) => build_tile_layer(
@@ -242,9 +405,9 @@ fn build_map(
                     }
                 }
                 update_colliders(&mut map, &units, layer);
-
-                commands.spawn().insert(MapLoaded);
+                update_terrain(&mut terrain, &units, layer);
             }
+            commands.spawn().insert(MapLoaded);
 
             state.set(GameState::BattleMap).unwrap();
         }
@@ -356,17 +519,43 @@ fn update_colliders(map: &mut CollisionMap, units: &MapUnits, layer: &MapLayer)
     }
 }
 
+/// Tiles tagged with a terrain enum in LDtk set the terrain under them. Tiles
+/// in later layers cover earlier ones.
+fn update_terrain(terrain: &mut TerrainMap, units: &MapUnits, layer: &MapLayer) {
+    if let MapLayer::Tiles(layer) = layer {
+        for tile in layer.tiles.iter() {
+            let kind = TerrainTile::ALL
+                .iter()
+                .find(|t| layer.has_enum(tile.id(), t.name()));
+            if let Some(kind) = kind {
+                let xy = units.xy_to_grid(tile.pixel_xy().as_vec2());
+                terrain.set(xy, *kind);
+            }
+        }
+    }
+}
+
 fn update_map_units(
     mut units: ResMut<MapUnits>,
-    q_units: Query<(Entity, &Transform), (With<MapUnit>, With<PlayerUnit>)>,
+    q_units: Query<
+        (Entity, &Transform, &UnitCommands, Option<&PlayerUnit>),
+        (With<MapUnit>, Or<(With<PlayerUnit>, With<EnemyUnit>)>),
+    >,
 ) {
     units.clear();
 
     // //println!("Count: {}", q_units.iter().count());
-    for (entity, transform) in q_units.iter() {
+    for (entity, transform, commands, player) in q_units.iter() {
+        let faction = match player {
+            Some(_) => Faction::Player,
+            None => Faction::Enemy,
+        };
         //println!("Inserting {:?} at {}", entity, transform.translation.xy());
         ///let i = units.xy_to_index(transform.translation.xy());
-        units.set_from_xy(transform.translation.xy(), entity);
+        units.set_from_xy(transform.translation.xy(), entity, faction);
+        if let Some(dest) = commands.moving_to() {
+            units.reserve(dest, entity, faction);
+        }
     }
 }
 
@@ -391,3 +580,59 @@ fn get_atlas(
         }
     }
 }
+
+#[test]
+fn friendly_parties_dont_stack() {
+    let mut units = MapUnits::new(IVec2::new(4, 4));
+    let (a, b, c) = (Entity::from_raw(0), Entity::from_raw(1), Entity::from_raw(2));
+    units.set_from_grid_xy(IVec2::new(1, 1), a, Faction::Player);
+    units.reserve(IVec2::new(2, 1), a, Faction::Player);
+
+    assert!(units.can_enter(IVec2::new(1, 1), a, Faction::Player));
+    assert!(!units.can_enter(IVec2::new(1, 1), b, Faction::Player));
+    assert!(!units.can_enter(IVec2::new(2, 1), b, Faction::Player));
+    assert!(units.can_enter(IVec2::new(2, 1), c, Faction::Enemy));
+    assert!(!units.is_free(IVec2::new(2, 1)));
+    assert!(units.is_free(IVec2::new(3, 3)));
+    assert!(!units.is_free(IVec2::new(4, 0)));
+}
+
+#[test]
+fn factions_meet() {
+    let mut units = MapUnits::new(IVec2::new(4, 4));
+    let (player, enemy) = (Entity::from_raw(0), Entity::from_raw(1));
+    units.set_from_grid_xy(IVec2::new(2, 3), player, Faction::Player);
+    assert_eq!(units.contested().count(), 0);
+
+    units.set_from_grid_xy(IVec2::new(2, 3), enemy, Faction::Enemy);
+    assert_eq!(units.get_from_grid_xy(IVec2::new(2, 3)), Some(player));
+    assert_eq!(units.get_faction(IVec2::new(2, 3), Faction::Enemy), Some(enemy));
+    let meetings: Vec<_> = units.contested().collect();
+    assert_eq!(meetings, vec![(IVec2::new(2, 3), player, enemy)]);
+}
+
+#[test]
+fn terrain_tiles_are_walkable() {
+    // Terrain costs only matter if some of each terrain's tiles aren't colliders
+    let map: serde_json::Value =
+        serde_json::from_str(include_str!("../../assets/ldtk/scenes/map.ldtk")).unwrap();
+    let tileset = map["defs"]["tilesets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["identifier"] == "Map_Pieces")
+        .unwrap();
+    let tagged = |name: &str| -> Vec<u64> {
+        tileset["enumTags"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["enumValueId"] == name)
+            .map(|t| t["tileIds"].as_array().unwrap().iter().filter_map(|i| i.as_u64()).collect())
+            .unwrap_or_default()
+    };
+    let colliders = tagged("Collider");
+    for terrain in ["Mountain", "Mud", "Water"] {
+        assert!(tagged(terrain).iter().any(|t| !colliders.contains(t)), "{}", terrain);
+    }
+}
@@ -2,6 +2,7 @@ use std::{collections::VecDeque, time::Duration};
 
 use bevy::prelude::*;
 use bevy_tiled_camera::TiledProjection;
+use rand::{prelude::IteratorRandom, Rng};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -12,25 +13,36 @@ use crate::{
 };
 
 use self::{
-    combat::MapCombatPlugin, enemies::BattleMapEnemyPlugin, input::InputPlugin, map::{MapPlugin, CollisionMap},
+    bases::BasesPlugin, combat::MapCombatPlugin, save::SavePlugin,
+    flow::FlowPlugin, fog::FogPlugin, population::PopulationPlugin,
+    waves::{schedule_name, WaveSchedules, WavesPlugin}, enemies::BattleMapEnemyPlugin, input::InputPlugin, map::{MapPlugin, CollisionMap},
     selection::BattleMapSelectionPlugin, 
     //spawn::MapSpawnPlugin, 
     units::UnitsPlugin, player::BattleMapPlayerPlugin, spawn::MapSpawnPlugin,
 };
 
+mod ai;
+mod bases;
 mod combat;
 mod components;
 mod enemies;
+mod flow;
+mod fog;
 mod input;
 mod map;
+mod pathing;
 mod selection;
 mod spawn;
 mod units;
+mod waves;
 mod player;
+mod population;
+mod save;
 mod setup;
 
 pub use components::*;
-pub use map::MapUnits;
+pub use ai::MapAi;
+pub use map::{Faction, MapUnits, TerrainTile};
 
 pub struct BattleMapPlugin;
 
@@ -45,6 +57,12 @@ impl Plugin for BattleMapPlugin {
             .add_plugin(UnitsPlugin)
             .add_plugin(BattleMapPlayerPlugin)
             .add_plugin(MapCombatPlugin)
+            .add_plugin(BasesPlugin)
+            .add_plugin(SavePlugin)
+            .add_plugin(WavesPlugin)
+            .add_plugin(PopulationPlugin)
+            .add_plugin(FogPlugin)
+            .add_plugin(FlowPlugin)
             .add_system_set(SystemSet::on_enter(GameState::LoadBattleMap).with_system(load_map));
     }
 }
@@ -53,10 +71,16 @@ fn load_map(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
     configs: Res<Assets<ConfigAsset>>,
+    mut schedules: ResMut<WaveSchedules>,
 ) {
     let config = configs.get(SETTINGS_PATH).unwrap();
     let handle: Handle<LdtkMap> = asset_server.load(&config.settings.map_file);
     commands.insert_resource(handle);
+
+    for path in config.settings.wave_schedules.iter() {
+        let handle = asset_server.load(path.as_str());
+        schedules.0.insert(schedule_name(path).to_owned(), handle);
+    }
 }
 
 #[derive(Component)]
@@ -90,8 +114,13 @@ enum UnitCommand {
 
 #[derive(Component)]
 struct UnitCommands {
+    /// Seconds to move onto a grass tile. Moves onto other terrain are scaled
+    /// by its cost.
+    move_time: f32,
     move_timer: Timer,
     wait_timer: Timer,
+    /// Seconds the current move has been held up by a friendly party.
+    blocked_time: f32,
     queue: VecDeque<UnitCommand>,
     current: Option<UnitCommand>,
 }
@@ -100,14 +129,17 @@ struct UnitCommands {
 impl UnitCommands {
     pub fn new(move_time: f32, wait_time: f32) -> Self {
         let cmd = Self {
+            move_time,
             move_timer: Timer::from_seconds(move_time, false),
             wait_timer: Timer::from_seconds(wait_time, false),
+            blocked_time: 0.0,
             queue: VecDeque::new(),
             current: None,
         };
         cmd
     }
     fn next(&mut self) -> bool {
+        self.blocked_time = 0.0;
         self.current = self.queue.pop_front();
         if let Some(current) = self.current {
             //println!("Setting current command to {:?}", current);
@@ -125,12 +157,28 @@ impl UnitCommands {
         };
         self.queue.push_back(command);
     }
+    /// The tile the unit has started moving onto, if it's moving.
+    pub fn moving_to(&self) -> Option<IVec2> {
+        match self.current {
+            Some(UnitCommand::MoveToTile(_, b)) if !self.move_timer.elapsed().is_zero() => Some(b),
+            _ => None,
+        }
+    }
+
     /// Does not clear current action - unit will
     /// finish what it's currently doing.
     pub fn clear(&mut self) {
         self.queue.clear();
         //self.current = None;
     }
+
+    /// Drop every command, including the current one.
+    pub fn cancel(&mut self) {
+        self.queue.clear();
+        self.current = None;
+        self.blocked_time = 0.0;
+        self.move_timer.reset();
+    }
 }
 
 #[derive(Bundle, Default)]
@@ -144,6 +192,7 @@ impl MapUnitBundle {
     pub fn with_commands(commands: &[UnitCommand], move_time: f32, wait_time: f32) -> Self {
         let mut me = Self {
             commands: UnitCommands {
+                move_time,
                 move_timer: Timer::from_seconds(move_time, false),
                 wait_timer: Timer::from_seconds(wait_time, false),
                 ..Default::default()
@@ -163,8 +212,10 @@ impl MapUnitBundle {
 impl Default for UnitCommands {
     fn default() -> Self {
         Self {
+            move_time: 0.6,
             move_timer: Timer::from_seconds(0.6, false),
             wait_timer: Timer::from_seconds(0.3, false),
+            blocked_time: 0.0,
             queue: Default::default(),
             current: Default::default(),
         }
@@ -190,10 +241,22 @@ fn get_valid_spawn_points<'a>(
 ) -> Option<impl Iterator<Item=IVec2> + 'a> {
     let valid = ADJACENT.iter().filter(move |adj| {
         let adj = xy + IVec2::from(**adj);
-        return units.get_from_grid_xy(adj).is_none() && !colliders.0.is_obstacle(adj.to_array());
+        return units.is_free(adj) && !colliders.0.is_obstacle(adj.to_array());
     }).map(move |p| xy + IVec2::from(*p));
     if valid.clone().count() > 0 {
         return Some(valid);
     }
     None
+}
+
+/// A random free tile next to `xy` to put a new party on, in world space.
+fn spawn_point(
+    xy: IVec2,
+    units: &MapUnits,
+    colliders: &CollisionMap,
+    rng: &mut impl Rng,
+) -> Option<Vec3> {
+    let spawn = get_valid_spawn_points(xy, units, colliders)?.choose(rng)?;
+    let spawn = units.grid_to_xy(spawn);
+    Some(Vec3::new(spawn.x, spawn.y, 2.0))
 }
\ No newline at end of file
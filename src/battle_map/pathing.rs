@@ -0,0 +1,113 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::map::TerrainTile;
+
+pub const DIRECTIONS: [IVec2; 4] = [
+    IVec2::new(0, 1),
+    IVec2::new(1, 0),
+    IVec2::new(0, -1),
+    IVec2::new(-1, 0),
+];
+
+/// Find the cheapest path from `a` to `b` on a grid of `size`, moving in the
+/// four cardinal directions. `cost` gives the cost of stepping onto a tile, or
+/// `None` if it can't be entered. Both ends are included in the path.
+pub fn find_path(
+    a: IVec2,
+    b: IVec2,
+    size: IVec2,
+    cost: impl Fn(IVec2) -> Option<u32>,
+) -> Option<Vec<IVec2>> {
+    let in_bounds = |p: IVec2| !(p.cmplt(IVec2::ZERO).any() || p.cmpge(size).any());
+    if !in_bounds(a) || !in_bounds(b) {
+        return None;
+    }
+
+    // Manhattan distance at the cheapest terrain cost never overestimates
+    let min_cost = TerrainTile::ALL.iter().map(|t| t.move_cost()).min().unwrap_or(1);
+    let heuristic = |p: IVec2| {
+        let d = (b - p).abs();
+        (d.x + d.y) as u32 * min_cost
+    };
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::default();
+    let mut costs: HashMap<IVec2, u32> = HashMap::default();
+    open.push(Reverse((heuristic(a), a.to_array())));
+    costs.insert(a, 0);
+
+    while let Some(Reverse((_, curr))) = open.pop() {
+        let curr = IVec2::from(curr);
+        if curr == b {
+            let mut path = vec![curr];
+            let mut p = curr;
+            while let Some(prev) = came_from.get(&p) {
+                path.push(*prev);
+                p = *prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let curr_cost = costs[&curr];
+        for dir in DIRECTIONS {
+            let next = curr + dir;
+            if !in_bounds(next) {
+                continue;
+            }
+            let step = match cost(next) {
+                Some(step) => step,
+                None => continue,
+            };
+            let next_cost = curr_cost + step;
+            if costs.get(&next).map_or(true, |c| next_cost < *c) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, curr);
+                open.push(Reverse((next_cost + heuristic(next), next.to_array())));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn prefers_cheap_terrain() {
+    // A river across the middle that can be walked around at the top
+    let size = IVec2::new(5, 5);
+    let cost = |p: IVec2| {
+        if p.x == 2 && p.y != 4 {
+            Some(TerrainTile::Water.move_cost())
+        } else {
+            Some(TerrainTile::Dirt.move_cost())
+        }
+    };
+    let path = find_path(IVec2::new(0, 3), IVec2::new(4, 3), size, cost).unwrap();
+    assert_eq!(path.first(), Some(&IVec2::new(0, 3)));
+    assert_eq!(path.last(), Some(&IVec2::new(4, 3)));
+    assert!(!path.contains(&IVec2::new(2, 3)));
+    assert!(path.contains(&IVec2::new(2, 4)));
+}
+
+#[test]
+fn detours_around_a_costly_tile() {
+    // Going around one water tile on dirt is cheaper than wading through it
+    let water = IVec2::new(1, 1);
+    let cost = |p: IVec2| match p == water {
+        true => Some(TerrainTile::Water.move_cost()),
+        false => Some(TerrainTile::Dirt.move_cost()),
+    };
+    let path = find_path(IVec2::new(0, 1), IVec2::new(2, 1), IVec2::new(3, 3), cost).unwrap();
+    assert!(!path.contains(&water));
+    assert_eq!(path.len(), 5);
+}
+
+#[test]
+fn blocked_paths() {
+    let size = IVec2::new(3, 3);
+    let wall = |p: IVec2| if p.x == 1 { None } else { Some(1) };
+    assert_eq!(find_path(IVec2::ZERO, IVec2::new(2, 0), size, wall), None);
+    assert_eq!(find_path(IVec2::ZERO, IVec2::new(5, 0), size, |_| Some(1)), None);
+    assert_eq!(find_path(IVec2::ZERO, IVec2::ZERO, size, |_| Some(1)), Some(vec![IVec2::ZERO]));
+}
@@ -1,13 +1,13 @@
 use bevy::{prelude::*, ecs::system::EntityCommands, math::Vec3Swizzles};
-use rand::{thread_rng, prelude::IteratorRandom, Rng};
+use rand::{prelude::IteratorRandom, Rng};
 
 use crate::{ldtk_loader::{LdtkMap, Tags, Fields}, GameState, AtlasHandles, animation::Animator, SETTINGS_PATH, config::ConfigAsset, 
 //SpawnPrefabOld, 
 prefab::Prefabs, TILE_SIZE, battle_map::{PlayerUnit, UnitCommands}, party::{GenerateParty, Party, PartyUnit}, 
 
-GENERATE_PARTY_SYSTEM, LdtkHandles, unit::Player};
+GENERATE_PARTY_SYSTEM, LdtkHandles, rng::{GameRng, PARTY_STREAM, SPAWN_STREAM}, party_template::PartyTemplate, unit::Player};
 
-use super::{map::{BUILD_MAP_SYSTEM, CollisionMap}, spawn::Spawner, MapUnit, BattleMapEntity, MapUnits, get_valid_spawn_points, PlayerBase};
+use super::{map::{BUILD_MAP_SYSTEM, CollisionMap}, spawn::{Spawner, SpawnTemplate}, MapUnit, BattleMapEntity, MapUnits, get_valid_spawn_points, spawn_point, PlayerBase, bases::{BaseHealth, PLAYER_BASE_HP}, population::{PlayerParties, RecruitFeedback, RecruitParty, Treasury, under_cap}};
 
 //use super::Spawner;
 
@@ -41,74 +41,96 @@ fn setup(
     q_tags: Query<(Entity, &Tags, &Fields, &Name), (Added<Tags>, With<BattleMapEntity>)>,
     mut ldtk_handles: ResMut<LdtkHandles>,
     config: Res<Assets<ConfigAsset>>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     if let Some(config) = config.get(SETTINGS_PATH) {
         for (entity, tags, fields, name) in q_tags.iter() {
             if tags.has_all(&["player", "spawner"]) {
                 if let Some(min) = fields.try_get_f32("spawn_delay_min") {
                     if let Some(max) = fields.try_get_f32("spawn_delay_max") {
-                        let mut rng = thread_rng();
-                        let delay: f32 = rng.gen_range(min..max);
+                        let delay: f32 = game_rng.stream(SPAWN_STREAM).gen_range(min..max);
                         println!("Spawning player spawner!");
                         commands.entity(entity).insert(
                             Spawner(Timer::from_seconds(delay, true))
                         ).insert(Player)
                         ;
+                        if let Some(template) = fields.try_get_str("party") {
+                            commands.entity(entity).insert(SpawnTemplate(template.to_owned()));
+                        }
                     }
                 }
             }
             if tags.has("player_base") {
-                commands.entity(entity).insert(PlayerBase);
+                commands
+                    .entity(entity)
+                    .insert(PlayerBase)
+                    .insert(Player)
+                    .insert(BaseHealth::from_fields(fields, PLAYER_BASE_HP));
             }
         }
     }
 }
 
+/// Player parties are bought with gold rather than spawned on a timer.
 fn spawn(
     mut commands: Commands,
-    time: Res<Time>,
-    mut q_spawner: Query<(&Transform, &mut Spawner), With<Player>>,
+    mut ev_recruit: EventReader<RecruitParty>,
+    mut treasury: ResMut<Treasury>,
+    mut feedback: ResMut<RecruitFeedback>,
+    q_parties: PlayerParties,
+    q_spawner: Query<(&Transform, Option<&SpawnTemplate>), (With<Spawner>, With<Player>)>,
     map_units: Res<MapUnits>,
     colliders: Res<CollisionMap>,
     prefabs: Res<Prefabs>,
     ldtk: Res<Assets<LdtkMap>>,
     configs: Res<Assets<ConfigAsset>>,
+    templates: Res<Assets<PartyTemplate>>,
+    mut game_rng: ResMut<GameRng>,
 ) {
     if let Some(config) = configs.get(SETTINGS_PATH) {
-        for (transform, mut spawner) in q_spawner.iter_mut() {
+        if ev_recruit.iter().count() == 0 {
+            return;
+        }
+        // Ensure all our prefabs are loaded
+        for name in config.settings.player_units.iter() {
+            if ldtk.get(&**name).is_none() {
+                feedback.fail("Units are still loading");
+                return;
+            }
+        }
+        if !under_cap(q_parties.iter().count(), config.settings.player_party_cap) {
+            feedback.fail("Too many parties on the map");
+            return;
+        }
 
-            // Ensure all our prefabs are loaded
-            for name in config.settings.player_units.iter() {
-                if ldtk.get(&**name).is_none() {
+        if let Some((transform, template)) = q_spawner.iter().next() {
+            let curr = map_units.xy_to_grid(transform.translation.xy());
+            let pos = match spawn_point(curr, &map_units, &colliders, game_rng.stream(SPAWN_STREAM)) {
+                Some(pos) => pos,
+                None => {
+                    info!("No valid spot found to spawn!");
+                    feedback.fail("No room around the castle");
                     return;
                 }
+            };
+
+            if !treasury.spend(config.settings.party_cost) {
+                feedback.fail("Not enough gold");
+                return;
             }
-    
-            spawner.tick(time.delta());
-    
-            if spawner.just_finished() {
-    
-                let p = transform.translation;
-                let curr = map_units.xy_to_grid(p.xy());
-                let spawn_points = get_valid_spawn_points(curr, &map_units, &colliders);
-    
-                if let Some(spawn_points) = spawn_points {
-                    let mut rng = thread_rng(); 
-                    let spawn = spawn_points.choose(&mut rng).unwrap();
-                    let spawn = map_units.grid_to_xy(spawn);
-                    
-                    let pos = Vec3::new(spawn.x, spawn.y, 2.0);
-    
-                    let names = config.settings.player_units.iter().map(|s|s.to_string()).collect();
-                    //println!("Spawning gen...");
-                    commands.spawn().insert(
-                        GenerateParty::new(4, names, pos),
-                    ).insert(Player);
-    
-                } else {
-                    info!("No valid spot found to spawn!");
+            feedback.clear();
+
+            let template = template.and_then(|t| {
+                let found = prefabs.party_template(&t.0, &templates);
+                if found.is_none() {
+                    warn!("Unknown party template {}", t.0);
                 }
-            }
+                found.cloned()
+            }).unwrap_or_else(|| PartyTemplate::random(&config.settings.player_units, 4));
+            let units = template.roll(game_rng.stream(PARTY_STREAM));
+            commands.spawn().insert(
+                GenerateParty::from_units(units, pos),
+            ).insert(Player);
         }
     }
 }
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use super::fog::FogHidden;
+use crate::{
+    config::ConfigAsset,
+    party::{GenerateParty, Party, PartyUnit, ShowMapSprite},
+    unit::{Enemy, Player},
+    BuildPrefab, GameState, SETTINGS_PATH,
+};
+
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Treasury>()
+            .init_resource::<RecruitFeedback>()
+            .add_event::<RecruitParty>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::LoadBattleMap).with_system(setup_treasury),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::BattleMap)
+                    .with_system(earn_income)
+                    .with_system(cleanup_parties)
+                    .with_system(recruit_ui),
+            );
+    }
+}
+
+/// Gold the player spends to recruit parties at their castle.
+#[derive(Default, Debug)]
+pub struct Treasury {
+    pub gold: f32,
+}
+
+impl Treasury {
+    /// Take `cost` gold if there's enough of it.
+    pub fn spend(&mut self, cost: i32) -> bool {
+        if self.gold < cost as f32 {
+            return false;
+        }
+        self.gold -= cost as f32;
+        true
+    }
+}
+
+/// Sent when the player asks for a new party.
+pub struct RecruitParty;
+
+/// How long the reason a recruit failed stays up.
+const FEEDBACK_TIME: f32 = 3.0;
+
+/// Why the last attempt to recruit a party failed, shown under the recruit
+/// button for a few seconds.
+#[derive(Default)]
+pub struct RecruitFeedback {
+    message: Option<(&'static str, f32)>,
+}
+
+impl RecruitFeedback {
+    pub fn fail(&mut self, reason: &'static str) {
+        self.message = Some((reason, FEEDBACK_TIME));
+    }
+
+    pub fn clear(&mut self) {
+        self.message = None;
+    }
+}
+
+/// Parties on the map, counting the ones still being generated.
+pub type PartyFilter<Side> = (Or<(With<Party>, With<GenerateParty>)>, With<Side>);
+pub type PlayerParties<'w, 's> = Query<'w, 's, (), PartyFilter<Player>>;
+pub type EnemyParties<'w, 's> = Query<'w, 's, (), PartyFilter<Enemy>>;
+
+pub fn under_cap(live: usize, cap: usize) -> bool {
+    live < cap
+}
+
+fn setup_treasury(mut treasury: ResMut<Treasury>, configs: Res<Assets<ConfigAsset>>) {
+    if let Some(config) = configs.get(SETTINGS_PATH) {
+        treasury.gold = config.settings.starting_gold as f32;
+    }
+}
+
+fn earn_income(
+    time: Res<Time>,
+    mut treasury: ResMut<Treasury>,
+    configs: Res<Assets<ConfigAsset>>,
+) {
+    if let Some(config) = configs.get(SETTINGS_PATH) {
+        treasury.gold += config.settings.gold_per_second * time.delta_seconds();
+    }
+}
+
+/// Parties whose units have all died are removed along with their sprites.
+/// A visible party that lost the unit it was shown with picks another one.
+fn cleanup_parties(
+    mut commands: Commands,
+    q_parties: Query<(Entity, Option<&Children>, Option<&FogHidden>), With<Party>>,
+    q_units: Query<&PartyUnit>,
+    q_building: Query<(), With<BuildPrefab>>,
+    q_vis: Query<&Visibility>,
+) {
+    for (party, children, hidden) in q_parties.iter() {
+        // Wait for new parties to finish building their units
+        if children.map_or(false, |c| c.iter().any(|e| q_building.get(*e).is_ok())) {
+            continue;
+        }
+        let units: Vec<_> = children
+            .map(|c| c.iter().filter(|e| q_units.get(**e).is_ok()).cloned().collect())
+            .unwrap_or_default();
+        if units.is_empty() {
+            commands.entity(party).despawn_recursive();
+            continue;
+        }
+        // Parties under the fog are meant to be hidden
+        if hidden.is_some() {
+            continue;
+        }
+        let shown = units.iter().any(|unit| {
+            let sprite = q_units.get(*unit).unwrap().map_sprite();
+            q_vis.get(sprite).map_or(false, |v| v.is_visible)
+        });
+        if !shown {
+            commands.entity(units[0]).insert(ShowMapSprite);
+        }
+    }
+}
+
+fn recruit_ui(
+    mut egui: ResMut<EguiContext>,
+    time: Res<Time>,
+    treasury: Res<Treasury>,
+    mut feedback: ResMut<RecruitFeedback>,
+    configs: Res<Assets<ConfigAsset>>,
+    q_player: PlayerParties,
+    mut ev_recruit: EventWriter<RecruitParty>,
+) {
+    if let Some((_, t)) = &mut feedback.message {
+        *t -= time.delta_seconds();
+        if *t <= 0.0 {
+            feedback.clear();
+        }
+    }
+    if let Some(config) = configs.get(SETTINGS_PATH) {
+        let settings = &config.settings;
+        let live = q_player.iter().count();
+        egui::Area::new("recruit")
+            .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+            .show(egui.ctx_mut(), |ui| {
+                ui.label(format!("Gold: {}", treasury.gold.floor()));
+                ui.label(format!("Parties: {}/{}", live, settings.player_party_cap));
+                let enabled = treasury.gold >= settings.party_cost as f32
+                    && under_cap(live, settings.player_party_cap);
+                let button = egui::Button::new(format!("Recruit party ({} gold)", settings.party_cost));
+                if ui.add_enabled(enabled, button).clicked() {
+                    ev_recruit.send(RecruitParty);
+                }
+                if let Some((message, _)) = feedback.message {
+                    ui.label(message);
+                }
+            });
+    }
+}
+
+#[test]
+fn spending() {
+    let mut treasury = Treasury { gold: 25.5 };
+    assert!(treasury.spend(20));
+    assert!(!treasury.spend(20));
+    assert_eq!(treasury.gold, 5.5);
+
+    assert!(under_cap(2, 3));
+    assert!(!under_cap(3, 3));
+}
@@ -0,0 +1,361 @@
+use std::{fs, path::Path, time::Duration};
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::deck::EarnedCards,
+    party::{GenerateParty, Party, UnitPrefab},
+    start_screen::ContinueCampaign,
+    unit::{Enemy, Player, Stats, UnitCards},
+    GameState, SAVE_PATH, TILE_SIZE,
+};
+
+use super::{
+    bases::BaseHealth,
+    population::Treasury,
+    spawn::Spawner,
+    waves::{SpawnedBy, WaveSpawner, WaveState},
+    EnemyUnit, MapUnit, PlayerUnit,
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::BattleMap)
+                .with_system(save_on_key)
+                .with_system(restore)
+                .with_system(restore_units),
+        );
+    }
+}
+
+/// Everything needed to pick a campaign back up on the battle map.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveData {
+    pub state: GameState,
+    pub parties: Vec<SavedParty>,
+    pub spawners: Vec<SavedSpawner>,
+    #[serde(default)]
+    pub waves: Vec<SavedWaves>,
+    pub bases: Vec<SavedBase>,
+    #[serde(default)]
+    pub gold: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedParty {
+    pub player: bool,
+    pub pos: [f32; 3],
+    pub units: Vec<SavedUnit>,
+    #[serde(default)]
+    pub earned: Vec<String>,
+    /// Grid position of the wave spawner the party came from, so it keeps
+    /// counting toward that spawner's live parties.
+    #[serde(default)]
+    pub spawned_by: Option<[i32; 2]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedUnit {
+    pub prefab: String,
+    pub stats: Stats,
+    pub cards: Vec<String>,
+}
+
+/// Spawners and bases are part of the map, so they are matched back up by
+/// their grid position.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedSpawner {
+    pub xy: [i32; 2],
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedWaves {
+    pub xy: [i32; 2],
+    pub wave: usize,
+    pub cycle: u32,
+    pub pending: usize,
+    pub elapsed: f32,
+    pub duration: f32,
+    pub done: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedBase {
+    pub xy: [i32; 2],
+    pub health: BaseHealth,
+}
+
+impl SaveData {
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, ron::Error> {
+        ron::de::from_str(text)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::from_ron(&text)?)
+    }
+}
+
+/// Saved stats and cards to put back on a party's units once they're built.
+#[derive(Component)]
+struct RestoreUnits(Vec<SavedUnit>);
+
+fn grid_xy(transform: &Transform) -> [i32; 2] {
+    (transform.translation.xy().as_ivec2() / TILE_SIZE).to_array()
+}
+
+fn save_on_key(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    q_parties: Query<
+        (
+            &Transform,
+            &Children,
+            Option<&PlayerUnit>,
+            Option<&EarnedCards>,
+            Option<&SpawnedBy>,
+        ),
+        (With<MapUnit>, Or<(With<PlayerUnit>, With<EnemyUnit>)>),
+    >,
+    q_transforms: Query<&Transform>,
+    q_units: Query<(&UnitPrefab, &Stats, &UnitCards)>,
+    q_spawners: Query<(&Transform, &Spawner)>,
+    q_waves: Query<(&Transform, &WaveSpawner)>,
+    q_bases: Query<(&Transform, &BaseHealth)>,
+    treasury: Res<Treasury>,
+) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    let parties = q_parties
+        .iter()
+        .map(|(transform, children, player, earned, spawned_by)| SavedParty {
+            player: player.is_some(),
+            pos: transform.translation.to_array(),
+            units: children
+                .iter()
+                .filter_map(|child| q_units.get(*child).ok())
+                .map(|(prefab, stats, cards)| SavedUnit {
+                    prefab: prefab.0.clone(),
+                    stats: stats.clone(),
+                    cards: cards.0.clone(),
+                })
+                .collect(),
+            earned: earned.map(|e| e.0.clone()).unwrap_or_default(),
+            spawned_by: spawned_by
+                .and_then(|s| q_transforms.get(s.0).ok())
+                .map(grid_xy),
+        })
+        .collect();
+
+    let data = SaveData {
+        state: *state.current(),
+        parties,
+        spawners: q_spawners
+            .iter()
+            .map(|(transform, spawner)| SavedSpawner {
+                xy: grid_xy(transform),
+                elapsed: spawner.elapsed_secs(),
+                duration: spawner.duration().as_secs_f32(),
+            })
+            .collect(),
+        waves: q_waves
+            .iter()
+            .filter_map(|(transform, spawner)| {
+                let state = spawner.state.as_ref()?;
+                Some(SavedWaves {
+                    xy: grid_xy(transform),
+                    wave: state.wave,
+                    cycle: state.cycle,
+                    pending: state.pending,
+                    elapsed: state.timer.elapsed_secs(),
+                    duration: state.timer.duration().as_secs_f32(),
+                    done: state.done,
+                })
+            })
+            .collect(),
+        bases: q_bases
+            .iter()
+            .map(|(transform, health)| SavedBase {
+                xy: grid_xy(transform),
+                health: *health,
+            })
+            .collect(),
+        gold: treasury.gold,
+    };
+
+    match data.write(SAVE_PATH) {
+        Ok(_) => info!("Saved campaign to {}", SAVE_PATH),
+        Err(e) => warn!("Unable to save campaign to {}: {}", SAVE_PATH, e),
+    }
+}
+
+/// Restore a saved campaign after "Continue" was picked on the start screen.
+/// Waits for the map's bases to be set up so spawners and bases can be
+/// matched to the save.
+fn restore(
+    mut commands: Commands,
+    cont: Option<Res<ContinueCampaign>>,
+    mut state: ResMut<State<GameState>>,
+    mut q_spawners: Query<(&Transform, &mut Spawner)>,
+    mut q_waves: Query<(Entity, &Transform, &mut WaveSpawner)>,
+    mut q_bases: Query<(&Transform, &mut BaseHealth)>,
+    mut treasury: ResMut<Treasury>,
+) {
+    if cont.is_none() || q_bases.is_empty() {
+        return;
+    }
+    commands.remove_resource::<ContinueCampaign>();
+
+    let data = match SaveData::read(SAVE_PATH) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Unable to load campaign from {}: {}", SAVE_PATH, e);
+            return;
+        }
+    };
+
+    for (transform, mut spawner) in q_spawners.iter_mut() {
+        let xy = grid_xy(transform);
+        if let Some(saved) = data.spawners.iter().find(|s| s.xy == xy) {
+            spawner.set_duration(Duration::from_secs_f32(saved.duration));
+            spawner.set_elapsed(Duration::from_secs_f32(saved.elapsed));
+        }
+    }
+
+    for (_, transform, mut spawner) in q_waves.iter_mut() {
+        let xy = grid_xy(transform);
+        if let Some(saved) = data.waves.iter().find(|w| w.xy == xy) {
+            let mut timer = Timer::from_seconds(saved.duration, false);
+            timer.set_elapsed(Duration::from_secs_f32(saved.elapsed));
+            spawner.state = Some(WaveState {
+                wave: saved.wave,
+                cycle: saved.cycle,
+                pending: saved.pending,
+                timer,
+                done: saved.done,
+            });
+        }
+    }
+
+    treasury.gold = data.gold;
+
+    for (transform, mut health) in q_bases.iter_mut() {
+        let xy = grid_xy(transform);
+        if let Some(saved) = data.bases.iter().find(|b| b.xy == xy) {
+            *health = saved.health;
+        }
+    }
+
+    for party in data.parties.iter().filter(|p| !p.units.is_empty()) {
+        let names = party.units.iter().map(|u| u.prefab.clone()).collect();
+        let pos = Vec3::from(party.pos);
+        let mut entity = commands.spawn();
+        entity
+            .insert(GenerateParty::from_units(names, pos))
+            .insert(RestoreUnits(party.units.clone()));
+        if !party.earned.is_empty() {
+            entity.insert(EarnedCards(party.earned.clone()));
+        }
+        let spawner = party.spawned_by.and_then(|xy| {
+            q_waves
+                .iter()
+                .find(|(_, transform, _)| grid_xy(transform) == xy)
+                .map(|(spawner, _, _)| spawner)
+        });
+        if let Some(spawner) = spawner {
+            entity.insert(SpawnedBy(spawner));
+        }
+        match party.player {
+            true => entity.insert(Player),
+            false => entity.insert(Enemy),
+        };
+    }
+
+    if data.state != GameState::BattleMap {
+        state.set(data.state).unwrap();
+    }
+}
+
+/// Put saved stats and cards back on a restored party once its units have
+/// been built from their prefabs.
+fn restore_units(
+    mut commands: Commands,
+    q_parties: Query<(Entity, &Children, &RestoreUnits), With<Party>>,
+    mut q_units: Query<(&mut Stats, &mut UnitCards)>,
+) {
+    for (party, children, restore) in q_parties.iter() {
+        if children.iter().any(|c| q_units.get(*c).is_err()) {
+            continue;
+        }
+        for (child, saved) in children.iter().zip(restore.0.iter()) {
+            if let Ok((mut stats, mut cards)) = q_units.get_mut(*child) {
+                *stats = saved.stats.clone();
+                cards.0 = saved.cards.clone();
+            }
+        }
+        commands.entity(party).remove::<RestoreUnits>();
+    }
+}
+
+#[test]
+fn save_round_trip() {
+    let data = SaveData {
+        state: GameState::BattleMap,
+        parties: vec![SavedParty {
+            player: true,
+            pos: [64.0, 128.0, 2.0],
+            units: vec![SavedUnit {
+                prefab: "ldtk/prefabs/units_wizard.ldtk".to_string(),
+                stats: Stats::new(10, 2, 1),
+                cards: vec!["abilities/fireball.card".to_string()],
+            }],
+            earned: vec!["abilities/smite.card".to_string()],
+            spawned_by: Some([3, 4]),
+        }],
+        spawners: vec![SavedSpawner { xy: [3, 4], elapsed: 1.5, duration: 4.0 }],
+        waves: vec![SavedWaves {
+            xy: [3, 4],
+            wave: 2,
+            cycle: 1,
+            pending: 3,
+            elapsed: 0.5,
+            duration: 10.0,
+            done: false,
+        }],
+        bases: vec![SavedBase { xy: [18, 2], health: BaseHealth::new(20) }],
+        gold: 12.5,
+    };
+
+    let text = data.to_ron().unwrap();
+    let loaded = SaveData::from_ron(&text).unwrap();
+    assert_eq!(loaded.state, GameState::BattleMap);
+    assert_eq!(loaded.parties[0].units[0].stats.hp(), 10);
+    assert_eq!(loaded.parties[0].earned, data.parties[0].earned);
+    assert_eq!(loaded.parties[0].spawned_by, Some([3, 4]));
+    assert_eq!(loaded.spawners[0].xy, [3, 4]);
+    assert_eq!(loaded.waves[0].wave, 2);
+    assert_eq!(loaded.waves[0].elapsed, 0.5);
+    assert_eq!(loaded.bases[0].health, BaseHealth::new(20));
+    assert_eq!(loaded.gold, 12.5);
+}
@@ -1,12 +1,12 @@
 use bevy::{math::Vec3Swizzles, prelude::*};
 
-use sark_pathfinding::AStar;
-
 use crate::{config::ConfigAsset, make_sprite, GameState, SETTINGS_PATH, TILE_SIZE};
 
 use super::{
+    fog::FogMap,
     input::{Cursor, TileClickedEvent},
-    map::CollisionMap,
+    map::{CollisionMap, Faction, TerrainMap},
+    pathing::find_path,
     MapUnits, PlayerUnit, UnitCommands, UnitCommand,
 };
 
@@ -38,6 +38,9 @@ fn on_select(
     mut q_unit_commands: Query<&mut UnitCommands>,
     configs: Res<Assets<ConfigAsset>>,
     map: Res<CollisionMap>,
+    fog: Res<FogMap>,
+    terrain: Res<TerrainMap>,
+    map_units: Res<MapUnits>,
     q_pos: Query<&Transform>,
     q_player: Query<&PlayerUnit>,
     q_highlight: Query<Entity, With<HighlightSprite>>,
@@ -72,7 +75,13 @@ fn on_select(
                     return;
                 }
                 //println!("Trying to get path from {} to {}", a, b);
-                selection.path = get_path(a, b, &map);
+                let known = fog.known_obstacles(&map);
+                // Other player parties are walked around rather than through
+                let blocked = |p: IVec2| {
+                    known.is_obstacle(p.to_array())
+                        || !map_units.can_enter(p, selected, Faction::Player)
+                };
+                selection.path = get_path(a, b, blocked, &terrain);
                 if selection.path.is_some() {
                     //println!("Found path. Selection state: {:?}", selection);
                 }
@@ -116,13 +125,17 @@ fn on_select(
     }
 }
 
-fn get_path(a: IVec2, b: IVec2, map: &CollisionMap) -> Option<Vec<IVec2>> {
-    let mut astar = AStar::new(10);
-    if let Some(path) = astar.find_path(&map.0, a.into(), b.into()) {
-        //let offset = map.half_offset();
-        return Some(path.iter().map(|p| IVec2::from(*p)).collect::<Vec<IVec2>>());
-    }
-    None
+/// Find the cheapest path that avoids `blocked` tiles.
+fn get_path(
+    a: IVec2,
+    b: IVec2,
+    blocked: impl Fn(IVec2) -> bool,
+    terrain: &TerrainMap,
+) -> Option<Vec<IVec2>> {
+    find_path(a, b, terrain.size(), |p| match blocked(p) {
+        true => None,
+        false => Some(terrain.move_cost(p)),
+    })
 }
 
 #[derive(Component)]
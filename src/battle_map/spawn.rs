@@ -35,6 +35,10 @@ impl std::ops::DerefMut for Spawner {
     }
 }
 
+/// Name of the party template a spawner builds its parties from.
+#[derive(Component)]
+pub struct SpawnTemplate(pub String);
+
 #[derive(Component)]
 pub struct DespawnTimer(Timer);
 
@@ -2,12 +2,15 @@ use std::{cmp::Ordering, collections::VecDeque, time::Duration};
 
 use bevy::{math::Vec3Swizzles, prelude::*};
 
-use rand::{distributions::WeightedIndex, prelude::Distribution, thread_rng, Rng};
-use sark_pathfinding::AStar;
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
 
-use crate::{GameState, TILE_SIZE};
+use crate::{rng::{GameRng, MAP_AI_STREAM}, unit::Stats, GameState, TILE_SIZE};
 
-use super::{map::CollisionMap, PlayerBase, PlayerUnit, MapUnit, UnitCommands, UnitCommand};
+use super::{ai::{choose_goal, GuardPost, MapAi, MapGoal}, flow::{FlowFields, FLOW_FIELD_SYSTEM}, map::{CollisionMap, Faction, TerrainMap}, pathing::find_path, MapUnits, PlayerBase, PlayerUnit, MapUnit, UnitCommands, UnitCommand};
+
+/// How long a player party waits on a friendly party in its way before
+/// giving up on its orders.
+const PLAYER_BLOCKED_TIME: f32 = 2.0;
 
 pub struct UnitsPlugin;
 
@@ -15,7 +18,8 @@ impl Plugin for UnitsPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(SystemSet::on_update(GameState::BattleMap))
             .add_system_set(
-                SystemSet::on_update(GameState::BattleMap).with_system(process_commands),
+                SystemSet::on_update(GameState::BattleMap)
+                    .with_system(process_commands.after(FLOW_FIELD_SYSTEM)),
             )
             ;
     }
@@ -25,28 +29,39 @@ impl Plugin for UnitsPlugin {
 fn process_commands(
     time: Res<Time>,
     mut q_set: QuerySet<(
-        QueryState<(Entity, &mut UnitCommands, &mut Transform)>,
-        QueryState<&Transform, With<PlayerUnit>>,
+        QueryState<(Entity, &mut UnitCommands, &mut Transform, Option<&PlayerUnit>)>,
+        QueryState<&Transform, (With<PlayerUnit>, With<MapUnit>)>,
         QueryState<&Transform, With<PlayerBase>>,
     )>,
     //map: Res<Map>,
-    mut map: ResMut<CollisionMap>,
+    map: Res<CollisionMap>,
+    terrain: Res<TerrainMap>,
+    flow: Res<FlowFields>,
+    mut map_units: ResMut<MapUnits>,
     mut player_positions: Local<Vec<IVec2>>,
+    mut game_rng: ResMut<GameRng>,
+    q_children: Query<&Children>,
+    q_ai: Query<&MapAi>,
+    q_stats: Query<&Stats>,
+    q_post: Query<&GuardPost>,
 ) {
     player_positions.clear();
     player_positions.extend(
         q_set
             .q1()
             .iter()
-            .map(|t| t.translation.xy().as_ivec2() - map.half_offset()),
+            .map(|t| t.translation.xy().as_ivec2() / TILE_SIZE),
     );
     //println!("{}", q_set.q2().iter().count());
-    let base_pos = q_set.q2().get_single();
-    let base_pos = base_pos.map(|p| p.translation.xy());
-    if base_pos.is_err() {
+    let base_pos = q_set.q2().get_single().ok().map(|p| p.translation.xy());
+    if base_pos.is_none() {
         //println!("Couldn't find base!");
     }
-    for (entity, mut unit_commands, mut transform) in q_set.q0().iter_mut() {
+    for (entity, mut unit_commands, mut transform, player) in q_set.q0().iter_mut() {
+        let faction = match player {
+            Some(_) => Faction::Player,
+            None => Faction::Enemy,
+        };
         //println!("{:?} Command count {}", entity, unit_commands.queue.len());
         if unit_commands.current.is_none() {
             unit_commands.next();
@@ -56,6 +71,25 @@ fn process_commands(
         if let Some(command) = unit_commands.current {
             match command {
                 UnitCommand::MoveToTile(a, b) => {
+                    if unit_commands.moving_to().is_none() {
+                        if !map_units.can_enter(b, entity, faction) {
+                            // A friendly party is in the way. Players wait a bit
+                            // for it to move on, the AI goes back to thinking.
+                            if player.is_none() {
+                                unit_commands.next();
+                            } else {
+                                unit_commands.blocked_time += time.delta_seconds();
+                                if unit_commands.blocked_time >= PLAYER_BLOCKED_TIME {
+                                    unit_commands.cancel();
+                                }
+                            }
+                            continue;
+                        }
+                        map_units.reserve(b, entity, faction);
+                    }
+                    // Slow or rough terrain takes longer to move onto
+                    let duration = unit_commands.move_time * terrain.get(b).move_scale();
+                    unit_commands.move_timer.set_duration(Duration::from_secs_f32(duration));
                     unit_commands.move_timer.tick(time.delta());
                     let t = unit_commands.move_timer.percent();
                     let a = a * TILE_SIZE;
@@ -82,11 +116,21 @@ fn process_commands(
                 }
                 UnitCommand::AiThink() => {
                     //println!("{:?} Thinking!", entity);
-                    let mut rng = thread_rng();
-                    let choices = ["wait", "attack"];
-                    let weights = [1_i32, 5];
-                    let dist = WeightedIndex::new(&weights).unwrap();
-                    if choices[dist.sample(&mut rng)] == "wait" {
+                    // A party follows the map AI of its first unit
+                    let units: Vec<Entity> = q_children
+                        .get(entity)
+                        .map_or(Vec::new(), |c| c.iter().copied().collect());
+                    let ai = units
+                        .iter()
+                        .find_map(|u| q_ai.get(*u).ok())
+                        .copied()
+                        .unwrap_or_default();
+
+                    let rng = game_rng.stream(MAP_AI_STREAM);
+                    let weights = [ai.wait_weight.max(0), ai.move_weight.max(0)];
+                    let wait = WeightedIndex::new(&weights)
+                        .map_or(false, |dist| dist.sample(rng) == 0);
+                    if wait {
                         let wait: f32 = rng.gen_range(0.15..1.5);
                         //println!("Slime {:?} is gonna wait for {} seconds!", entity, wait);
                         unit_commands.push(UnitCommand::Wait(wait));
@@ -96,51 +140,78 @@ fn process_commands(
                     }
 
                     let a = transform.translation.xy().as_ivec2() / TILE_SIZE;
-                    //let a -= collisiotin.axis_offset();
-                    if let Ok(base_pos) = base_pos {
-                        //println!("{:?} at {}, Finding nearest player {:?}", entity, a, player_positions);
-                        //let b = map.to_index_2d(b.as_vec2());
-                        let b = base_pos.as_ivec2() / TILE_SIZE;
-                        //println!("Slime to base {}, {}", a, b);
-                        let mut astar = AStar::new(10);
-                        let i = map.to_index(b.into());
-                        map.0.toggle_obstacle_index(i);
-                        if let Some(path) = astar.find_path(&map.0, a.into(), b.into()) {
-                            //println!("Path {:?}", path);
-                            if let Some(next) = path.get(1) {
-                                let b = IVec2::from(*next);
-                                //println!("Pathin {} to {} (no divide)", a, b);
-
-                                // let a = a + map.half_offset();
-                                // let b = b + map.half_offset();
-
-                                //println!("Should see 'done moving' next");
-                                unit_commands.push(UnitCommand::MoveToTile(a, b));
-                                unit_commands.push(UnitCommand::AiThink());
-                                //println!("{:?} pushed {} to {} to commands for move. Stack state: {:?}. Calling next",entity, a, b, unit_commands.queue);
-                                unit_commands.next();
-                            }
-                        } else {
-                            //println!("Couldn't find path!");
-                            // Couldn't find a player to path to. Go for the kill!
-                            //println!("Couldn't find a player!");
+                    let (hp, max_hp) = units
+                        .iter()
+                        .filter_map(|u| q_stats.get(*u).ok())
+                        .fold((0, 0), |(hp, max), s| (hp + s.hp(), max + s.max_hp()));
+                    let health = if max_hp > 0 { hp as f32 / max_hp as f32 } else { 1.0 };
+                    let home = q_post.get(entity).ok().map(|post| post.0);
+                    let base = base_pos.map(|p| p.as_ivec2() / TILE_SIZE);
+
+                    let next = match choose_goal(&ai, a, health, home, &player_positions, base) {
+                        // Player parties and the base have a flow field to follow
+                        MapGoal::MoveTo(b) if flow.get(b).is_some() => flow
+                            .get(b)
+                            .and_then(|field| {
+                                field.next_step(a, |p| map_units.can_enter(p, entity, faction))
+                            }),
+                        MapGoal::MoveTo(b) => {
+                            // The base is an obstacle, but it's where we're headed.
+                            // Other enemy parties are walked around.
+                            let cost = |p: IVec2| {
+                                let blocked = p != b && map.is_obstacle(p.to_array());
+                                match blocked || !map_units.can_enter(p, entity, faction) {
+                                    true => None,
+                                    false => Some(terrain.move_cost(p)),
+                                }
+                            };
+                            find_path(a, b, map.size().as_ivec2(), cost)
+                                .and_then(|path| path.get(1).copied())
+                        }
+                        MapGoal::FleeFrom(threat) => {
+                            let open = |p: IVec2| {
+                                !map.is_obstacle_bounds_checked(p)
+                                    && map_units.can_enter(p, entity, faction)
+                            };
+                            flee_step(a, threat, open)
+                        }
+                        MapGoal::Hold => None,
+                    };
+
+                    match next {
+                        Some(b) => {
+                            //println!("Pathin {} to {} (no divide)", a, b);
+                            unit_commands.push(UnitCommand::MoveToTile(a, b));
+                        }
+                        None => {
+                            // Nowhere to go right now, look again in a bit
+                            let wait: f32 = game_rng.stream(MAP_AI_STREAM).gen_range(0.15..1.5);
+                            unit_commands.push(UnitCommand::Wait(wait));
                         }
-                        map.0.toggle_obstacle_index(i);
                     }
+                    unit_commands.push(UnitCommand::AiThink());
+                    unit_commands.next();
                 }
             }
         }
     }
 }
 
-fn get_nearest_player_position(a: IVec2, positions: &Vec<IVec2>) -> Option<IVec2> {
-    let res = positions.iter().map(|b| (a - *b).as_vec2().length());
-    let res = res
-        .enumerate()
-        .min_by(|(_, a), (_, b)| a.partial_cmp(&b).unwrap_or(Ordering::Less))
-        .map(|(index, _)| index);
-    if let Some(i) = res {
-        return Some(positions[i]);
-    }
-    None
+/// The open tile next to `a` that gets furthest from `threat`, if any of
+/// them are further than `a` already is.
+fn flee_step(a: IVec2, threat: IVec2, open: impl Fn(IVec2) -> bool) -> Option<IVec2> {
+    let dist = |p: IVec2| (p - threat).as_vec2().length_squared();
+    [IVec2::X, -IVec2::X, IVec2::Y, -IVec2::Y]
+        .iter()
+        .map(|dir| a + *dir)
+        .filter(|p| open(*p) && dist(*p) > dist(a))
+        .max_by(|p, q| dist(*p).partial_cmp(&dist(*q)).unwrap_or(Ordering::Equal))
+}
+
+#[test]
+fn fleeing_moves_away() {
+    let (a, threat) = (IVec2::new(2, 2), IVec2::new(1, 2));
+    assert_eq!(flee_step(a, threat, |_| true), Some(IVec2::new(3, 2)));
+    // Cornered with only the threat's tile open
+    assert_eq!(flee_step(a, threat, |p| p == threat), None);
 }
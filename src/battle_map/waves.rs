@@ -0,0 +1,394 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadedAsset},
+    math::Vec3Swizzles,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ConfigAsset,
+    party::GenerateParty,
+    party_template::PartyTemplate,
+    prefab::Prefabs,
+    rng::{GameRng, PARTY_STREAM, SPAWN_STREAM},
+    unit::Enemy,
+    GameState, GENERATE_PARTY_SYSTEM, SETTINGS_PATH,
+};
+
+use super::{
+    map::CollisionMap,
+    population::{under_cap, EnemyParties},
+    spawn_point, MapUnits,
+};
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveSchedule>()
+            .add_asset_loader(WaveScheduleLoader)
+            .init_resource::<WaveSchedules>()
+            .add_system_set(
+                SystemSet::on_update(GameState::BattleMap)
+                    .with_system(spawn_waves.before(GENERATE_PARTY_SYSTEM)),
+            );
+    }
+}
+
+/// An authored list of enemy waves, loaded from a `.waves` file in
+/// `assets/waves`. Enemy spawners pick one by file name with their `waves`
+/// field.
+#[derive(TypeUuid, Debug, Clone, Serialize, Deserialize)]
+#[uuid = "3c9a7e51-2b4d-4f86-a1e0-6d8b9c2f4e17"]
+pub struct WaveSchedule {
+    pub waves: Vec<Wave>,
+    /// Most parties from this spawner that can be on the map at once.
+    #[serde(default = "default_max_live")]
+    pub max_live: usize,
+    /// Start over from the first wave once the last one has spawned.
+    #[serde(default)]
+    pub repeat: bool,
+    #[serde(default)]
+    pub escalation: Escalation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wave {
+    /// Name of the party template to spawn.
+    pub party: String,
+    /// Seconds to wait before this wave starts.
+    pub delay: f32,
+    /// How many parties the wave spawns.
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+/// How a repeating schedule gets harder each time it starts over.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Escalation {
+    /// Wave delays are multiplied by this once per cycle.
+    pub delay_scale: f32,
+    /// Delays never drop below this.
+    pub min_delay: f32,
+    /// Extra parties added to every wave per cycle.
+    pub extra_parties: usize,
+}
+
+impl Default for Escalation {
+    fn default() -> Self {
+        Self {
+            delay_scale: 1.0,
+            min_delay: 0.0,
+            extra_parties: 0,
+        }
+    }
+}
+
+fn default_max_live() -> usize {
+    usize::MAX
+}
+
+fn default_count() -> usize {
+    1
+}
+
+impl WaveSchedule {
+    /// Delay and party count of `wave` after `cycle` times through the schedule.
+    pub fn escalated(&self, wave: usize, cycle: u32) -> (f32, usize) {
+        let wave = &self.waves[wave];
+        let esc = &self.escalation;
+        let delay = (wave.delay * esc.delay_scale.powi(cycle as i32)).max(esc.min_delay);
+        let count = wave.count + esc.extra_parties * cycle as usize;
+        (delay, count)
+    }
+}
+
+/// Wave schedules by name.
+#[derive(Default)]
+pub struct WaveSchedules(pub HashMap<String, Handle<WaveSchedule>>);
+
+/// The name a wave schedule is referred to by, from its asset path.
+pub fn schedule_name(path: &str) -> &str {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    file.strip_suffix(".waves").unwrap_or(file)
+}
+
+/// Where a spawner is in its schedule.
+#[derive(Debug, Clone)]
+pub struct WaveState {
+    pub wave: usize,
+    pub cycle: u32,
+    /// Parties of the current wave still waiting to spawn.
+    pub pending: usize,
+    pub timer: Timer,
+    pub done: bool,
+}
+
+impl WaveState {
+    pub fn new(schedule: &WaveSchedule) -> Self {
+        let mut state = Self {
+            wave: 0,
+            cycle: 0,
+            pending: 0,
+            timer: Timer::default(),
+            done: schedule.waves.is_empty(),
+        };
+        if !state.done {
+            state.start_wave(schedule);
+        }
+        state
+    }
+
+    fn start_wave(&mut self, schedule: &WaveSchedule) {
+        let (delay, _) = schedule.escalated(self.wave, self.cycle);
+        self.timer = Timer::from_seconds(delay, false);
+    }
+
+    /// Advance the schedule. Returns the party template due to spawn this tick,
+    /// if any. At most one party spawns per tick, and only while fewer than
+    /// `max_live` of this spawner's parties are alive. The party stays due
+    /// until [WaveState::spawned] is called, so one that couldn't be placed is
+    /// tried again.
+    pub fn tick(&mut self, schedule: &WaveSchedule, delta: Duration, live: usize) -> Option<String> {
+        if self.done {
+            return None;
+        }
+        if self.pending == 0 {
+            if !self.timer.tick(delta).finished() {
+                return None;
+            }
+            self.pending = schedule.escalated(self.wave, self.cycle).1;
+        }
+        if live >= schedule.max_live {
+            return None;
+        }
+
+        Some(schedule.waves[self.wave].party.clone())
+    }
+
+    /// The party returned by the last tick made it onto the map.
+    pub fn spawned(&mut self, schedule: &WaveSchedule) {
+        if self.done || self.pending == 0 {
+            return;
+        }
+        self.pending -= 1;
+        if self.pending == 0 {
+            self.next_wave(schedule);
+        }
+    }
+
+    fn next_wave(&mut self, schedule: &WaveSchedule) {
+        self.wave += 1;
+        if self.wave >= schedule.waves.len() {
+            if !schedule.repeat {
+                self.done = true;
+                return;
+            }
+            self.wave = 0;
+            self.cycle += 1;
+        }
+        self.start_wave(schedule);
+    }
+
+    /// Seconds until the next wave starts, if one is coming.
+    pub fn countdown(&self) -> Option<f32> {
+        if self.done || self.pending > 0 {
+            return None;
+        }
+        Some((self.timer.duration() - self.timer.elapsed()).as_secs_f32())
+    }
+}
+
+/// An enemy spawner following a wave schedule instead of a random timer.
+#[derive(Component)]
+pub struct WaveSpawner {
+    pub schedule: String,
+    /// Set up once the schedule has loaded.
+    pub state: Option<WaveState>,
+}
+
+impl WaveSpawner {
+    pub fn new(schedule: &str) -> Self {
+        Self {
+            schedule: schedule.to_owned(),
+            state: None,
+        }
+    }
+
+    pub fn countdown(&self) -> Option<f32> {
+        self.state.as_ref().and_then(WaveState::countdown)
+    }
+}
+
+/// The spawner a party came from.
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+fn spawn_waves(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_spawner: Query<(Entity, &Transform, &mut WaveSpawner), With<Enemy>>,
+    q_spawned: Query<&SpawnedBy>,
+    map_units: Res<MapUnits>,
+    colliders: Res<CollisionMap>,
+    schedules: Res<WaveSchedules>,
+    schedule_assets: Res<Assets<WaveSchedule>>,
+    prefabs: Res<Prefabs>,
+    templates: Res<Assets<PartyTemplate>>,
+    configs: Res<Assets<ConfigAsset>>,
+    q_parties: EnemyParties,
+    mut game_rng: ResMut<GameRng>,
+    mut unknown: Local<HashSet<String>>,
+) {
+    let config = match configs.get(SETTINGS_PATH) {
+        Some(config) => config,
+        None => return,
+    };
+    // Waves hold off while the enemy is at its population cap
+    if !under_cap(q_parties.iter().count(), config.settings.enemy_party_cap) {
+        return;
+    }
+    for (entity, transform, mut spawner) in q_spawner.iter_mut() {
+        let spawner = &mut *spawner;
+        let schedule = match schedules
+            .0
+            .get(&spawner.schedule)
+            .and_then(|handle| schedule_assets.get(handle))
+        {
+            Some(schedule) => schedule,
+            None => continue,
+        };
+        let state = spawner.state.get_or_insert_with(|| WaveState::new(schedule));
+
+        let live = q_spawned.iter().filter(|s| s.0 == entity).count();
+        let party = match state.tick(schedule, time.delta(), live) {
+            Some(party) => party,
+            None => continue,
+        };
+
+        // Parties that can't be spawned yet stay due and are tried again
+        let template = match prefabs.party_template(&party, &templates) {
+            Some(template) => template,
+            None => {
+                if unknown.insert(party.clone()) {
+                    warn!("Wave schedule {} uses unknown party template {}", spawner.schedule, party);
+                }
+                continue;
+            }
+        };
+
+        let curr = map_units.xy_to_grid(transform.translation.xy());
+        match spawn_point(curr, &map_units, &colliders, game_rng.stream(SPAWN_STREAM)) {
+            Some(pos) => {
+                let units = template.roll(game_rng.stream(PARTY_STREAM));
+                commands
+                    .spawn()
+                    .insert(GenerateParty::from_units(units, pos))
+                    .insert(Enemy)
+                    .insert(SpawnedBy(entity));
+                state.spawned(schedule);
+            }
+            // Retried every frame until there's room, so keep quiet about it
+            None => debug!("No valid spot found to spawn!"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WaveScheduleLoader;
+
+impl AssetLoader for WaveScheduleLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let schedule: WaveSchedule = ron::de::from_bytes(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(schedule));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves"]
+    }
+}
+
+#[cfg(test)]
+fn test_schedule() -> WaveSchedule {
+    ron::de::from_str(
+        r#"(
+            waves: [
+                (party: "a", delay: 2.0),
+                (party: "b", delay: 4.0, count: 2),
+            ],
+            max_live: 2,
+            repeat: true,
+            escalation: (delay_scale: 0.5, min_delay: 1.5, extra_parties: 1),
+        )"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn waves_in_order() {
+    let schedule = test_schedule();
+    let mut state = WaveState::new(&schedule);
+    let second = Duration::from_secs(1);
+
+    assert_eq!(state.countdown(), Some(2.0));
+    assert_eq!(state.tick(&schedule, second, 0), None);
+    assert_eq!(state.tick(&schedule, second, 0), Some("a".to_string()));
+    state.spawned(&schedule);
+
+    // Second wave spawns two parties, one per tick
+    assert_eq!(state.countdown(), Some(4.0));
+    for _ in 0..3 {
+        assert_eq!(state.tick(&schedule, second, 0), None);
+    }
+    assert_eq!(state.tick(&schedule, second, 0), Some("b".to_string()));
+    state.spawned(&schedule);
+    assert_eq!(state.countdown(), None);
+    assert_eq!(state.tick(&schedule, second, 0), Some("b".to_string()));
+    state.spawned(&schedule);
+
+    // Back to the start, faster and with an extra party
+    assert_eq!((state.wave, state.cycle), (0, 1));
+    assert_eq!(state.countdown(), Some(1.5));
+    assert_eq!(schedule.escalated(0, 1), (1.5, 2));
+}
+
+#[test]
+fn waves_respect_max_live() {
+    let schedule = test_schedule();
+    let mut state = WaveState::new(&schedule);
+    let second = Duration::from_secs(1);
+
+    state.tick(&schedule, second, 2);
+    assert_eq!(state.tick(&schedule, second, 2), None);
+    assert_eq!(state.tick(&schedule, second, 1), Some("a".to_string()));
+    assert_eq!(schedule_name("waves/cave.waves"), "cave");
+}
+
+#[test]
+fn blocked_parties_are_retried() {
+    let schedule = test_schedule();
+    let mut state = WaveState::new(&schedule);
+    let second = Duration::from_secs(1);
+
+    state.tick(&schedule, second, 0);
+    // Nowhere to put the party, so it's still due on later ticks
+    for _ in 0..3 {
+        assert_eq!(state.tick(&schedule, second, 0), Some("a".to_string()));
+    }
+    state.spawned(&schedule);
+    assert_eq!((state.wave, state.pending), (1, 0));
+    assert_eq!(state.tick(&schedule, second, 0), None);
+}
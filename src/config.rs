@@ -21,8 +21,13 @@ impl Plugin for ConfigPlugin {
 
 #[derive(Default, Deserialize, Serialize, Clone, Debug)]
 pub struct GameSettings {
+    /// Developer override to skip the start screen and jump straight to a state.
     #[serde(default)]
-    pub begin_state: GameState,
+    pub begin_state: Option<GameState>,
+    /// Seed for all gameplay randomness. A `--seed` argument takes precedence,
+    /// and a random seed is used if neither is given.
+    #[serde(default)]
+    pub seed: Option<u64>,
     #[serde(default)]
     pub map_move_speed: f32,
     #[serde(default)]
@@ -37,6 +42,33 @@ pub struct GameSettings {
     pub player_units: Vec<String>,
     #[serde(default)]
     pub enemy_units: Vec<String>,
+    /// Most parties each side can have on the battle map.
+    #[serde(default = "default_party_cap")]
+    pub player_party_cap: usize,
+    #[serde(default = "default_party_cap")]
+    pub enemy_party_cap: usize,
+    /// Gold it costs the player to recruit a party.
+    #[serde(default)]
+    pub party_cost: i32,
+    #[serde(default)]
+    pub starting_gold: i32,
+    #[serde(default)]
+    pub gold_per_second: f32,
+    /// Party templates spawners can refer to by file name.
+    #[serde(default)]
+    pub party_templates: Vec<String>,
+    /// Wave schedules enemy spawners can refer to by file name.
+    #[serde(default)]
+    pub wave_schedules: Vec<String>,
+    #[serde(default)]
+    pub cards: Vec<String>,
+    /// Cards given to units whose prefab doesn't list any.
+    #[serde(default)]
+    pub starter_cards: Vec<String>,
+}
+
+fn default_party_cap() -> usize {
+    8
 }
 
 #[derive(TypeUuid)]
@@ -10,7 +10,9 @@ use debug::DebugPlugin;
 use ldtk_loader::{LdtkPlugin, LdtkMap};
 use party::PartyPlugin;
 use prefab::{PrefabsPlugin, DoneLoadingPrefabs, LOAD_PREFAB_SYSTEM};
+use rng::{GameRng, RngPlugin};
 use serde::{Deserialize, Serialize};
+use start_screen::StartScreenPlugin;
 use unit::UnitsPlugin;
 use unit_test::UnitTestPlugin;
 
@@ -25,7 +27,10 @@ mod config;
 mod grid;
 mod ldtk_loader;
 mod party;
+mod party_template;
 mod prefab;
+mod rng;
+mod start_screen;
 mod unit;
 mod util;
 mod debug;
@@ -34,6 +39,7 @@ mod unit_test;
 pub use grid::*;
 
 pub const SETTINGS_PATH: &str = "game_settings.config";
+pub const SAVE_PATH: &str = "saves/campaign.ron";
 pub const GENERATE_PARTY_SYSTEM: &str = "generate_party";
 
 pub use util::*;
@@ -58,6 +64,9 @@ pub enum GameState {
     BattleMap,
     LoadArena,
     Arena,
+    ArenaResults,
+    Victory,
+    Defeat,
     AssetTestLoad,
     AssetTest,
     BeginningCombat,
@@ -80,6 +89,7 @@ pub fn main() {
         .init_resource::<LdtkHandles>()
         .add_plugins(DefaultPlugins)
         .add_plugin(ConfigPlugin)
+        .add_plugin(RngPlugin)
         .add_plugin(GameCameraPlugin)
         .add_plugin(EguiPlugin)
         .add_plugin(LdtkPlugin)
@@ -91,6 +101,7 @@ pub fn main() {
         .add_plugin(PrefabsPlugin)
         .add_plugin(PartyPlugin)
         .add_plugin(UnitsPlugin)
+        .add_plugin(StartScreenPlugin)
         // .add_plugin(DebugPlugin)
         .add_state(GameState::Starting)
         .add_startup_system(load_configs)
@@ -109,10 +120,16 @@ fn start(
     mut state: ResMut<State<GameState>>, 
     configs: Res<Assets<ConfigAsset>>,
     mut ev_prefab_load: EventReader<DoneLoadingPrefabs>,
+    mut rng: ResMut<GameRng>,
 ) {
     if let Some(config) = configs.get(SETTINGS_PATH) {
         for _ in ev_prefab_load.iter() {
-            state.set(config.settings.begin_state).unwrap();
+            if let (None, Some(seed)) = (rng::seed_from_args(), config.settings.seed) {
+                rng.reseed(seed);
+            }
+            info!("Random seed: {}", rng.seed());
+            let begin = config.settings.begin_state.unwrap_or(GameState::StartScreen);
+            state.set(begin).unwrap();
         }
         //println!("Loading state {:?}", config.settings.begin_state);
     }
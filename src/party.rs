@@ -1,15 +1,19 @@
 use bevy::prelude::*;
-use rand::{thread_rng, prelude::SliceRandom, Rng};
+use rand::{prelude::SliceRandom, Rng};
 
-use crate::{ldtk_loader::LdtkMap, GENERATE_PARTY_SYSTEM, TILE_SIZE, BuildPrefab};
+use crate::{ldtk_loader::LdtkMap, party_template::{PartyTemplate, PartyTemplateLoader}, rng::{GameRng, PARTY_STREAM}, GENERATE_PARTY_SYSTEM, TILE_SIZE, BuildPrefab};
+
+pub const SHOW_MAP_SPRITE_SYSTEM: &str = "show_map_sprite";
 
 pub struct PartyPlugin;
 
 impl Plugin for PartyPlugin {
     fn build(&self, app: &mut App) {
         app
+        .add_asset::<PartyTemplate>()
+        .add_asset_loader(PartyTemplateLoader)
         .add_system(generate.label(GENERATE_PARTY_SYSTEM))
-        .add_system(show_map_sprite)
+        .add_system(show_map_sprite.label(SHOW_MAP_SPRITE_SYSTEM))
         ;
 
     }
@@ -20,6 +24,8 @@ pub struct GenerateParty {
     count: usize,
     names: Vec<String>,
     pos: Vec3,
+    /// Spawn `names` as given, in order, instead of picking at random.
+    exact: bool,
 }
 impl GenerateParty {
     pub fn new(count: usize, names: Vec<String>, pos: Vec3) -> Self {
@@ -27,6 +33,17 @@ impl GenerateParty {
             count,
             names,
             pos,
+            exact: false,
+        }
+    }
+
+    /// A party made of exactly these units, in order.
+    pub fn from_units(names: Vec<String>, pos: Vec3) -> Self {
+        Self {
+            count: names.len(),
+            names,
+            pos,
+            exact: true,
         }
     }
 }
@@ -40,6 +57,10 @@ pub struct PartyUnit {
     pub arena_sprite: Entity,
 }
 
+/// The prefab a party unit was built from.
+#[derive(Component, Debug, Clone)]
+pub struct UnitPrefab(pub String);
+
 #[derive(Component)]
 pub struct ShowMapSprite;
 
@@ -66,8 +87,9 @@ fn generate(
     asset_server: Res<AssetServer>,
     ldtk: Res<Assets<LdtkMap>>,
     mut q_gen: Query<(Entity, &mut GenerateParty)>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    let mut rng = thread_rng();
+    let rng = game_rng.stream(PARTY_STREAM);
     for (entity, gen) in q_gen.iter_mut() {
         //println!("Generating party");
 
@@ -76,12 +98,21 @@ fn generate(
             continue;
         }
 
+        if gen.count == 0 {
+            warn!("Tried to spawn an empty party");
+            commands.entity(entity).despawn();
+            continue;
+        }
+
         let mut units = Vec::new();
 
         let icon = rng.gen_range(0..gen.count);
 
         for i in 0..gen.count {
-            let to_spawn = gen.names.choose(&mut rng).unwrap();
+            let to_spawn = match gen.exact {
+                true => &gen.names[i],
+                false => gen.names.choose(rng).unwrap(),
+            };
 
             let mut unit = commands.spawn();
             
@@ -109,7 +140,7 @@ fn generate(
     }
 }
 
-fn show_map_sprite(
+pub fn show_map_sprite(
     mut commands: Commands,
     q_unit: Query<(Entity,&PartyUnit), With<ShowMapSprite>>,
     mut q_vis: Query<&mut Visibility>,
@@ -0,0 +1,134 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use rand::{prelude::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+/// The makeup of a party, loaded from a `.party` file in `assets/parties`.
+///
+/// Templates are referred to by file name, so "parties/slime_pack.party" is
+/// "slime_pack". Spawners pick theirs with the `party` field.
+#[derive(TypeUuid, Debug, Clone, Default, Serialize, Deserialize)]
+#[uuid = "0e8c4d1a-7f3b-4b6e-9c2d-8a5f1e3b7c90"]
+pub struct PartyTemplate {
+    /// Units that are always in the party, by prefab path.
+    #[serde(default)]
+    pub units: Vec<String>,
+    /// Units the rest of the party is picked from.
+    #[serde(default)]
+    pub random: Vec<RandomSlot>,
+    /// Smallest and largest party size. Defaults to the number of fixed units.
+    #[serde(default)]
+    pub size: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomSlot {
+    pub unit: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl PartyTemplate {
+    /// `count` units picked evenly from `names`.
+    pub fn random(names: &[String], count: usize) -> Self {
+        Self {
+            units: Vec::new(),
+            random: names
+                .iter()
+                .map(|unit| RandomSlot {
+                    unit: unit.to_owned(),
+                    weight: 1.0,
+                })
+                .collect(),
+            size: Some((count, count)),
+        }
+    }
+
+    /// Every unit that could end up in the party.
+    pub fn unit_names(&self) -> impl Iterator<Item = &String> {
+        self.units.iter().chain(self.random.iter().map(|slot| &slot.unit))
+    }
+
+    /// Pick the units for a new party. Fixed units come first.
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<String> {
+        let (min, max) = self.size.unwrap_or((self.units.len(), self.units.len()));
+        let size = rng.gen_range(min..=max.max(min));
+
+        let mut units = self.units.clone();
+        while units.len() < size {
+            match self.random.choose_weighted(rng, |slot| slot.weight) {
+                Ok(slot) => units.push(slot.unit.clone()),
+                Err(_) => break,
+            }
+        }
+        units
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PartyTemplateLoader;
+
+impl AssetLoader for PartyTemplateLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let template: PartyTemplate = ron::de::from_bytes(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(template));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["party"]
+    }
+}
+
+/// The name a template is referred to by, from its asset path.
+pub fn template_name(path: &str) -> &str {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    file.strip_suffix(".party").unwrap_or(file)
+}
+
+#[cfg(test)]
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn fixed_units_first() {
+    let template: PartyTemplate = ron::de::from_str(
+        r#"(
+            units: ["slime", "slime", "slime"],
+            random: [(unit: "snake", weight: 1.0)],
+            size: Some((4, 4)),
+        )"#,
+    )
+    .unwrap();
+    let units = template.roll(&mut StdRng::seed_from_u64(0));
+    assert_eq!(units, vec!["slime", "slime", "slime", "snake"]);
+}
+
+#[test]
+fn size_range() {
+    let names = vec!["a".to_string(), "b".to_string()];
+    let mut template = PartyTemplate::random(&names, 2);
+    template.size = Some((2, 5));
+    let mut rng = StdRng::seed_from_u64(1);
+    for _ in 0..50 {
+        let units = template.roll(&mut rng);
+        assert!((2..=5).contains(&units.len()));
+        assert!(units.iter().all(|u| names.contains(u)));
+    }
+
+    assert_eq!(template_name("parties/slime_pack.party"), "slime_pack");
+}
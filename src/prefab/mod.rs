@@ -1,6 +1,6 @@
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{asset::LoadState, prelude::*, utils::HashMap};
 
-use crate::{config::ConfigAsset, GameState, SETTINGS_PATH, ldtk_loader::{PrefabEntity, LdtkMap, Tags, Fields}};
+use crate::{config::ConfigAsset, GameState, SETTINGS_PATH, ldtk_loader::{PrefabEntity, LdtkMap, Tags, Fields}, arena::card_asset::CardAsset, party_template::{PartyTemplate, template_name}};
 
 use self::unit::UnitPrefabPlugin;
 
@@ -19,13 +19,30 @@ pub struct Prefabs {
     pub map: HashMap<String, Handle<LdtkMap>>,
     pub player_units: HashMap<String, Handle<LdtkMap>>,
     pub enemy_units: HashMap<String, Handle<LdtkMap>>,
-    pub cards: HashMap<String, Handle<LdtkMap>>,
+    pub cards: HashMap<String, Handle<CardAsset>>,
+    /// Party templates by name.
+    pub parties: HashMap<String, Handle<PartyTemplate>>,
 }
 
 impl Prefabs {
     pub fn iter_units(&self) -> impl Iterator<Item=(&String,&Handle<LdtkMap>)> {
         self.player_units.iter().chain(self.enemy_units.iter())
     }
+
+    /// Card names sorted so they show up in a stable order.
+    pub fn card_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.cards.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn party_template<'a>(
+        &self,
+        name: &str,
+        templates: &'a Assets<PartyTemplate>,
+    ) -> Option<&'a PartyTemplate> {
+        self.parties.get(name).and_then(|handle| templates.get(handle))
+    }
 }
 
 pub struct PrefabsPlugin;
@@ -53,6 +70,8 @@ fn load_prefabs(
     mut ev_loaded: EventWriter<DoneLoadingPrefabs>,
     mut prefabs: ResMut<Prefabs>,
     ldtk: Res<Assets<LdtkMap>>,
+    cards: Res<Assets<CardAsset>>,
+    templates: Res<Assets<PartyTemplate>>,
 ) {
     if let Some(config) = config.get(SETTINGS_PATH) {
         for unit in config.settings.player_units.iter() {
@@ -69,6 +88,21 @@ fn load_prefabs(
             }
         }
 
+        for card in config.settings.cards.iter() {
+            if !prefabs.cards.contains_key(card) {
+                let handle = asset_server.load(card);
+                prefabs.cards.insert(card.to_owned(), handle);
+            }
+        }
+
+        for path in config.settings.party_templates.iter() {
+            let name = template_name(path);
+            if !prefabs.parties.contains_key(name) {
+                let handle = asset_server.load(path.as_str());
+                prefabs.parties.insert(name.to_owned(), handle);
+            }
+        }
+
         if prefabs.player_units.iter().any(|(_,handle)| ldtk.get(handle).is_none()) {
             return;
         }
@@ -77,6 +111,45 @@ fn load_prefabs(
             return;
         }
 
+        // Cards and templates that failed to load are skipped rather than
+        // waited on forever
+        let card_loading = |handle: &Handle<CardAsset>| {
+            cards.get(handle).is_none()
+                && asset_server.get_load_state(handle) != LoadState::Failed
+        };
+        if prefabs.cards.iter().any(|(_,handle)| card_loading(handle)) {
+            return;
+        }
+
+        let template_loading = |handle: &Handle<PartyTemplate>| {
+            templates.get(handle).is_none()
+                && asset_server.get_load_state(handle) != LoadState::Failed
+        };
+        if prefabs.parties.iter().any(|(_,handle)| template_loading(handle)) {
+            return;
+        }
+
+        for (path, handle) in prefabs.cards.iter() {
+            if cards.get(handle).is_none() {
+                warn!("Card {} failed to load", path);
+            }
+        }
+
+        for (name, handle) in prefabs.parties.iter() {
+            let template = match templates.get(handle) {
+                Some(template) => template,
+                None => {
+                    warn!("Party template {} failed to load", name);
+                    continue;
+                }
+            };
+            for unit in template.unit_names() {
+                if !prefabs.player_units.contains_key(unit) && !prefabs.enemy_units.contains_key(unit) {
+                    warn!("Party template {} uses unit {}, which isn't in player_units or enemy_units", name, unit);
+                }
+            }
+        }
+
         ev_loaded.send(DoneLoadingPrefabs);
     }
 
@@ -1,6 +1,6 @@
 use bevy::{prelude::*, utils::HashMap, ecs::system::EntityCommands};
 
-use crate::{ldtk_loader::{LdtkMap, MapTileset}, AtlasHandles, party::{PartyUnitSprite, PartyUnit}, TILE_SIZE, BuildPrefab};
+use crate::{arena::ai::AiStrategy, battle_map::MapAi, ldtk_loader::{LdtkMap, MapTileset}, AtlasHandles, party::{PartyUnitSprite, PartyUnit, UnitPrefab}, TILE_SIZE, BuildPrefab, unit::{Stats, Shields, UnitCards}};
 
 pub struct UnitPrefabPlugin;
 
@@ -39,8 +39,21 @@ fn build_prefab(
             arena_sprite: arena_sprite.clone()
         };
 
+        let root = ldtk.get_tagged("root").next().unwrap_or_else(||
+            panic!("Error spawning unit {}, missing root tag", ldtk.name())
+        );
+        let name = root.fields().try_get_str("name").unwrap_or(ldtk.name());
+        let stats = Stats::from_fields(root.fields());
+
          commands.entity(entity)
         .insert(unit)
+        .insert(UnitPrefab(build.name.clone()))
+        .insert(Name::new(name.to_owned()))
+        .insert(stats)
+        .insert(Shields::default())
+        .insert(UnitCards::from_fields(root.fields()))
+        .insert(AiStrategy::from_fields(root.fields()))
+        .insert(MapAi::from_fields(root.fields()))
         .insert(Transform::default())
         .insert(GlobalTransform::default())
         .add_child(map_sprite)
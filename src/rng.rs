@@ -0,0 +1,108 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+
+/// Random choices made while generating parties.
+pub const PARTY_STREAM: &str = "party";
+/// Spawner delays and spawn points on the battle map.
+pub const SPAWN_STREAM: &str = "spawn";
+/// Enemy decisions on the battle map.
+pub const MAP_AI_STREAM: &str = "map_ai";
+/// Deck shuffles and draws in the arena.
+pub const DECK_STREAM: &str = "deck";
+/// Enemy card and target choices in the arena.
+pub const ARENA_AI_STREAM: &str = "arena_ai";
+/// Card rewards after a fight.
+pub const REWARD_STREAM: &str = "reward";
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = seed_from_args().unwrap_or_else(|| thread_rng().gen());
+        app.insert_resource(GameRng::new(seed));
+    }
+}
+
+/// The source of every random choice in gameplay. Each system draws from its
+/// own named stream so adding a roll in one system doesn't shift the results
+/// of another. The same seed always gives the same rolls.
+pub struct GameRng {
+    seed: u64,
+    streams: HashMap<String, StdRng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Start over from a new seed, resetting every stream.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    pub fn stream(&mut self, name: &str) -> &mut StdRng {
+        let seed = self.seed ^ stream_hash(name);
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| StdRng::seed_from_u64(seed))
+    }
+}
+
+/// FNV-1a, so stream seeds don't depend on the std hasher.
+fn stream_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Read the seed from a `--seed <n>` or `--seed=<n>` command line argument.
+pub fn seed_from_args() -> Option<u64> {
+    let args: Vec<String> = std::env::args().collect();
+    parse_seed(&args)
+}
+
+fn parse_seed(args: &[String]) -> Option<u64> {
+    for (i, arg) in args.iter().enumerate() {
+        if let Some(seed) = arg.strip_prefix("--seed=") {
+            return seed.parse().ok();
+        }
+        if arg == "--seed" {
+            return args.get(i + 1)?.parse().ok();
+        }
+    }
+    None
+}
+
+#[test]
+fn same_seed_same_rolls() {
+    let mut a = GameRng::new(42);
+    let mut b = GameRng::new(42);
+    // Drawing from another stream first must not change the results.
+    b.stream(MAP_AI_STREAM).gen::<u32>();
+    let a: Vec<u32> = (0..8).map(|_| a.stream(SPAWN_STREAM).gen()).collect();
+    let b: Vec<u32> = (0..8).map(|_| b.stream(SPAWN_STREAM).gen()).collect();
+    assert_eq!(a, b);
+
+    let mut rng = GameRng::new(42);
+    let first: u32 = rng.stream(PARTY_STREAM).gen();
+    rng.reseed(42);
+    assert_eq!(first, rng.stream(PARTY_STREAM).gen::<u32>());
+    assert_ne!(first, GameRng::new(43).stream(PARTY_STREAM).gen::<u32>());
+}
+
+#[test]
+fn seed_args() {
+    let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(parse_seed(&args(&["game", "--seed", "12"])), Some(12));
+    assert_eq!(parse_seed(&args(&["game", "--seed=7"])), Some(7));
+    assert_eq!(parse_seed(&args(&["game"])), None);
+}
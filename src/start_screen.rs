@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_egui::{egui, EguiContext};
+
+use crate::{config::ConfigAsset, GameState, SAVE_PATH, SETTINGS_PATH};
+
+pub struct StartScreenPlugin;
+
+impl Plugin for StartScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::StartScreen).with_system(menu));
+    }
+}
+
+/// Inserted when the player picks "Continue", so the battle map is restored
+/// from the save file instead of starting fresh.
+pub struct ContinueCampaign;
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+#[derive(Default)]
+struct MenuState {
+    show_settings: bool,
+}
+
+fn menu(
+    mut commands: Commands,
+    mut egui: ResMut<EguiContext>,
+    mut state: ResMut<State<GameState>>,
+    mut configs: ResMut<Assets<ConfigAsset>>,
+    mut exit: EventWriter<AppExit>,
+    mut menu: Local<MenuState>,
+) {
+    let ctx = egui.ctx_mut();
+
+    let mut next = None;
+    egui::Window::new("Bevy Card Game")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if ui.button("New Game").clicked() {
+                commands.remove_resource::<ContinueCampaign>();
+                next = Some(GameState::LoadBattleMap);
+            }
+            if save_exists() && ui.button("Continue").clicked() {
+                commands.insert_resource(ContinueCampaign);
+                next = Some(GameState::LoadBattleMap);
+            }
+            if ui.button("Settings").clicked() {
+                menu.show_settings = !menu.show_settings;
+            }
+            if ui.button("Quit").clicked() {
+                exit.send(AppExit);
+            }
+        });
+
+    if menu.show_settings {
+        if let Some(config) = configs.get_mut(SETTINGS_PATH) {
+            let settings = &mut config.settings;
+            egui::Window::new("Settings")
+                .collapsible(false)
+                .open(&mut menu.show_settings)
+                .show(ctx, |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut settings.map_move_speed, 0.1..=2.0)
+                            .text("Map move time"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut settings.map_move_wait, 0.0..=1.0)
+                            .text("Map move wait"),
+                    );
+                });
+        }
+    }
+
+    if let Some(next) = next {
+        state.set(next).unwrap();
+    }
+}
@@ -1,35 +1,209 @@
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{party::PartyUnit, ArenaSpriteVisibility};
+use crate::{ldtk_loader::Fields, party::PartyUnit, ArenaSpriteVisibility};
 
 pub const UNITS_DEF_FILE: &str = "units.ldtk";
 
-#[derive(Component, Serialize, Deserialize, Default)]
+/// How many tiles a unit can see on the battle map if its prefab doesn't say.
+pub const DEFAULT_SIGHT: i32 = 4;
+
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Stats {
     hp: i32,
     max_hp: i32,
     strength: i32,
     defense: i32,
+    #[serde(default = "default_sight")]
+    sight: i32,
     #[serde(default)]
     affinity: Option<Affinity>,
     #[serde(default)]
     resistance: Option<Resistance>,
 }
 
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+impl Stats {
+    pub fn new(hp: i32, strength: i32, defense: i32) -> Self {
+        Self {
+            hp,
+            max_hp: hp,
+            strength,
+            defense,
+            sight: DEFAULT_SIGHT,
+            ..Default::default()
+        }
+    }
+
+    /// Read stats from the fields of a unit prefab's root entity.
+    pub fn from_fields(fields: &Fields) -> Self {
+        let hp = fields.try_get_i32("hp").unwrap_or(1);
+        let strength = fields.try_get_i32("strength").unwrap_or(0);
+        let defense = fields.try_get_i32("defense").unwrap_or(0);
+        let sight = fields.try_get_i32("sight").unwrap_or(DEFAULT_SIGHT);
+
+        let element_value = |element: &str, value: &str| {
+            let element = fields.try_get_str(element).and_then(Element::from_name)?;
+            Some((element, fields.try_get_f32(value).unwrap_or(0.0)))
+        };
+
+        Self {
+            affinity: element_value("affinity", "affinity_value")
+                .map(|(element, value)| Affinity::new(element, value)),
+            resistance: element_value("resistance", "resistance_value")
+                .map(|(element, value)| Resistance::new(element, value)),
+            sight,
+            ..Self::new(hp, strength, defense)
+        }
+    }
+
+    pub fn hp(&self) -> i32 {
+        self.hp
+    }
+
+    pub fn max_hp(&self) -> i32 {
+        self.max_hp
+    }
+
+    pub fn strength(&self) -> i32 {
+        self.strength
+    }
+
+    pub fn defense(&self) -> i32 {
+        self.defense
+    }
+
+    pub fn sight(&self) -> i32 {
+        self.sight
+    }
+
+    pub fn affinity(&self) -> Option<Affinity> {
+        self.affinity
+    }
+
+    pub fn resistance(&self) -> Option<Resistance> {
+        self.resistance
+    }
+
+    pub fn with_affinity(mut self, affinity: Affinity) -> Self {
+        self.affinity = Some(affinity);
+        self
+    }
+
+    pub fn with_resistance(mut self, resistance: Resistance) -> Self {
+        self.resistance = Some(resistance);
+        self
+    }
+
+    pub fn with_bonus_defense(mut self, bonus: i32) -> Self {
+        self.defense += bonus;
+        self
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0
+    }
+
+    pub fn take_damage(&mut self, amount: i32) {
+        self.hp = (self.hp - amount).max(0);
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.hp = (self.hp + amount).min(self.max_hp);
+    }
+}
+
+fn default_sight() -> i32 {
+    DEFAULT_SIGHT
+}
+
+/// Damage dealt by an attack of the given `power`. The attacker's strength is
+/// added and the defender's defense subtracted, but a hit always does at least 1.
+pub fn physical_damage(power: i32, attacker: &Stats, defender: &Stats) -> i32 {
+    (power + attacker.strength - defender.defense).max(1)
+}
+
+/// Cards a unit brings to its party's deck, referred to by asset path.
+#[derive(Component, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct UnitCards(pub Vec<String>);
+
+impl UnitCards {
+    /// Read the `cards` array field from a unit prefab's root entity.
+    pub fn from_fields(fields: &Fields) -> Self {
+        let cards = fields.try_get_array("cards", |v| v.as_str().unwrap_or_default().to_string());
+        Self(cards.unwrap_or_default())
+    }
+}
+
+/// Bonus to outgoing damage of a single element. A value of 0.5 adds 50%.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Affinity {
     element: Element,
     value: f32,
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+impl Affinity {
+    pub fn new(element: Element, value: f32) -> Self {
+        Self { element, value }
+    }
+
+    pub fn element(&self) -> Element {
+        self.element
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+/// Reduction to incoming damage of a single element. A value of 0.5 blocks 50%.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct Resistance {
     element: Element,
     value: f32,
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+impl Resistance {
+    pub fn new(element: Element, value: f32) -> Self {
+        Self { element, value }
+    }
+
+    pub fn element(&self) -> Element {
+        self.element
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+/// Elemental shields on a unit. Each one absorbs the next hit of its element.
+#[derive(Component, Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Shields(Vec<Element>);
+
+impl Shields {
+    pub fn add(&mut self, element: Element) {
+        self.0.push(element);
+    }
+
+    pub fn has(&self, element: Element) -> bool {
+        self.0.contains(&element)
+    }
+
+    /// Consume a shield of the given element, returns false if there was none.
+    pub fn absorb(&mut self, element: Element) -> bool {
+        if let Some(i) = self.0.iter().position(|e| *e == element) {
+            self.0.remove(i);
+            return true;
+        }
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Element {
     Fire,
     Lightning,
@@ -37,6 +211,16 @@ pub enum Element {
     Holy,
 }
 impl Element {
+    pub fn from_name(name: &str) -> Option<Element> {
+        match name.to_lowercase().as_str() {
+            "fire" => Some(Element::Fire),
+            "lightning" => Some(Element::Lightning),
+            "death" => Some(Element::Death),
+            "holy" => Some(Element::Holy),
+            _ => None,
+        }
+    }
+
     pub fn get_sprite_id(&self) -> usize {
         match self {
             Element::Fire => 0,
@@ -97,4 +281,28 @@ fn set_position(
         t.translation = set.0;
         commands.entity(entity).remove::<SetPosition>();
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn damage_uses_strength_against_defense() {
+    let attacker = Stats::new(10, 3, 0);
+    let defender = Stats::new(10, 0, 2);
+    assert_eq!(physical_damage(4, &attacker, &defender), 5);
+}
+
+#[test]
+fn damage_is_at_least_one() {
+    let attacker = Stats::new(10, 0, 0);
+    let defender = Stats::new(10, 0, 50);
+    assert_eq!(physical_damage(4, &attacker, &defender), 1);
+}
+
+#[test]
+fn dies_at_zero_hp() {
+    let mut stats = Stats::new(5, 0, 0);
+    stats.take_damage(3);
+    assert!(!stats.is_dead());
+    stats.take_damage(3);
+    assert!(stats.is_dead());
+    assert_eq!(stats.hp(), 0);
+}